@@ -0,0 +1,213 @@
+//! Graphics adapter discovery and selection
+
+use crate::agents::Agent;
+use crate::config::Config;
+use crate::environment::EnvCell;
+use crate::error::InitError;
+use crate::hdr;
+use crate::organisms::Bond;
+use crate::profiler::Profiler;
+use crate::spatial_hash::HashGrid;
+use crate::stats::GpuStats;
+
+/// Features the simulation cannot run without
+pub const REQUIRED_FEATURES: wgpu::Features = wgpu::Features::CLEAR_TEXTURE;
+
+/// Graphics API to run on
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// Whichever the platform supports
+    All,
+    Vulkan,
+    Metal,
+    Dx12,
+    /// OpenGL / GLES, e.g. for llvmpipe on machines without a GPU
+    Gl,
+}
+
+impl Backend {
+    pub fn backends(self) -> wgpu::Backends {
+        match self {
+            Self::All => wgpu::Backends::all(),
+            Self::Vulkan => wgpu::Backends::VULKAN,
+            Self::Metal => wgpu::Backends::METAL,
+            Self::Dx12 => wgpu::Backends::DX12,
+            Self::Gl => wgpu::Backends::GL,
+        }
+    }
+}
+
+/// Which adapter to prefer when several can run the simulation
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerPreference {
+    /// Let the platform decide
+    None,
+    /// Usually an integrated GPU
+    Low,
+    /// Usually a discrete GPU
+    High,
+}
+
+impl PowerPreference {
+    fn wgpu(self) -> wgpu::PowerPreference {
+        match self {
+            Self::None => wgpu::PowerPreference::None,
+            Self::Low => wgpu::PowerPreference::LowPower,
+            Self::High => wgpu::PowerPreference::HighPerformance,
+        }
+    }
+}
+
+pub fn create_instance(config: &Config) -> wgpu::Instance {
+    wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: config.backend.backends(),
+        ..Default::default()
+    })
+}
+
+/// Picks the adapter asked for in the config that can present to `surface`
+pub async fn select_adapter(
+    instance: &wgpu::Instance,
+    surface: &wgpu::Surface<'_>,
+    config: &Config,
+) -> Result<wgpu::Adapter, InitError> {
+    let Some(name) = &config.adapter else {
+        return instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: config.power_preference.wgpu(),
+                compatible_surface: Some(surface),
+                force_fallback_adapter: config.fallback_adapter,
+            })
+            .await
+            .ok_or(InitError::NoAdapter);
+    };
+
+    let adapters = instance.enumerate_adapters(config.backend.backends());
+    let available = adapters.iter().map(|a| a.get_info().name).collect();
+    adapters
+        .into_iter()
+        .find(|adapter| {
+            let info = adapter.get_info();
+            info.name.to_lowercase().contains(&name.to_lowercase())
+                && (!config.fallback_adapter || info.device_type == wgpu::DeviceType::Cpu)
+                && adapter.is_surface_supported(surface)
+        })
+        .ok_or_else(|| InitError::AdapterNotFound {
+            name: name.clone(),
+            available,
+        })
+}
+
+/// Limits needed for a world of `width` x `height` cells and `agent_capacity` agent slots.
+/// Storage binding counts are taken from the bind group layouts, so they follow buffers added
+/// to the compute passes.
+pub fn required_limits(width: u32, height: u32, agent_capacity: u32) -> wgpu::Limits {
+    let layouts = [
+        Agent::bind_layout_desc(),
+        EnvCell::bind_layout_desc(),
+        GpuStats::bind_layout_desc(),
+        HashGrid::bind_layout_desc(),
+        Bond::bind_layout_desc(),
+    ];
+    let count = |is_kind: fn(&wgpu::BindingType) -> bool| {
+        layouts
+            .iter()
+            .map(|layout| layout.entries.iter().filter(|e| is_kind(&e.ty)).count() as u32)
+            .max()
+            .unwrap_or(0)
+    };
+    let storage_buffers = count(|ty| {
+        matches!(
+            ty,
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { .. },
+                ..
+            }
+        )
+    });
+    let storage_textures = count(|ty| matches!(ty, wgpu::BindingType::StorageTexture { .. }));
+
+    let (w, h) = (width as usize, height as usize);
+    let largest_buffer = [
+        EnvCell::buf_init_desc(w, h).size,
+        EnvCell::deposit_buf_desc(w, h).size,
+        EnvCell::blur_scratch_buf_desc(w, h).size,
+        agent_capacity as u64 * std::mem::size_of::<Agent>() as u64,
+    ]
+    .into_iter()
+    .max()
+    .unwrap_or(0);
+    let defaults = wgpu::Limits::downlevel_defaults();
+    wgpu::Limits {
+        max_texture_dimension_2d: width.max(height),
+        max_storage_buffers_per_shader_stage: storage_buffers,
+        max_storage_textures_per_shader_stage: storage_textures,
+        max_storage_buffer_binding_size: u32::try_from(largest_buffer)
+            .unwrap_or(u32::MAX)
+            .max(defaults.max_storage_buffer_binding_size),
+        max_buffer_size: largest_buffer.max(defaults.max_buffer_size),
+        ..defaults
+    }
+}
+
+/// Each limit in `required` that the adapter falls short of, as `name: needs x, adapter has y`
+pub fn missing_limits(adapter: &wgpu::Adapter, required: &wgpu::Limits) -> Vec<String> {
+    let mut missing = Vec::new();
+    required.check_limits_with_fail_fn(&adapter.limits(), false, |name, needed, allowed| {
+        missing.push(format!(
+            "{}: needs {}, adapter has {}",
+            name, needed, allowed
+        ));
+    });
+    missing
+}
+
+/// Prints the adapters on the configured backends, with whether each can run a world of
+/// `width` x `height` cells
+pub fn list_adapters(config: &Config, width: u32, height: u32) {
+    let instance = create_instance(config);
+    let adapters = instance.enumerate_adapters(config.backend.backends());
+    if adapters.is_empty() {
+        println!("No adapters found");
+        return;
+    }
+    let required = required_limits(width, height, config.agent_capacity());
+    for (i, adapter) in adapters.iter().enumerate() {
+        let info = adapter.get_info();
+        println!(
+            "{}: {} ({:?}, {:?})",
+            i, info.name, info.backend, info.device_type
+        );
+        if !info.driver.is_empty() || !info.driver_info.is_empty() {
+            println!("   driver: {} {}", info.driver, info.driver_info);
+        }
+
+        let missing = REQUIRED_FEATURES - adapter.features();
+        if missing.is_empty() {
+            println!("   required features: ok");
+        } else {
+            println!("   required features: missing {:?}", missing);
+        }
+        let missing = missing_limits(adapter, &required);
+        if missing.is_empty() {
+            println!("   required limits: ok");
+        } else {
+            println!("   required limits: missing");
+            for limit in missing {
+                println!("      {}", limit);
+            }
+        }
+        let profiling = if Profiler::features(adapter.features()).is_empty() {
+            "no, CPU timing only"
+        } else {
+            "yes"
+        };
+        println!("   GPU profiling: {}", profiling);
+        let textures = if hdr::supports_hdr(adapter) {
+            "float"
+        } else {
+            "8-bit, no float storage support"
+        };
+        println!("   sim textures: {}", textures);
+    }
+}
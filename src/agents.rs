@@ -3,43 +3,117 @@ use rand::{
     thread_rng,
 };
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, Default)]
-pub struct Agent {
-    position: [f32; 2],
-    angle: f32,
-    turn_speed: f32,
+use crate::config::Config;
+use crate::layout::shader_struct;
+use crate::organisms;
+
+shader_struct! {
+    #[repr(C)]
+    #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, Default)]
+    pub struct Agent {
+        position: [f32; 2],
+        // Only used with `--particle-life`, where it replaces the fixed speed and turning
+        velocity: [f32; 2],
+        angle: f32,
+        turn_speed: f32,
+        // Only used with `--lifecycle`
+        energy: f32,
+        age: u32,
+        // Zero for a free slot, which the agent pass copies through untouched
+        alive: u32,
+        // Heritable traits, mutated when an agent splits
+        sensor_spread: f32,
+        sensor_distance: f32,
+        speed: f32,
+        turn_strength: f32,
+        // SPECIES_PREY or SPECIES_PREDATOR, or with `--particle-life` an index into the
+        // interaction matrix; passed on to offspring
+        species: u32,
+    }
 }
 
-pub const NUM_AGENTS: usize = 10_000;
+/// Values of `Agent::species`, must match the `SPECIES_*` constants in the agent shader
+pub const SPECIES_PREY: u32 = 0;
+pub const SPECIES_PREDATOR: u32 = 1;
+
+/// Byte offset of the births counter in the lifecycle buffer, which is cleared every step
+pub const BIRTH_COUNT_OFFSET: u64 = 4;
+/// Upper bound on `--sensor-count`, must match `MAX_SENSORS` in the agent shader
+pub const MAX_SENSORS: u32 = 16;
+
+/// How agents turn in response to their sensor readings
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SteeringRule {
+    /// Turn towards the strongest sensor by a fixed fraction of its angle
+    Discrete,
+    /// Turn towards the reading-weighted mean sensor angle
+    Gradient,
+}
+
+impl SteeringRule {
+    /// Value of the agent shader's `steering` uniform, must match the `STEERING_*` constants
+    pub fn shader_id(self) -> u32 {
+        match self {
+            Self::Discrete => 0,
+            Self::Gradient => 1,
+        }
+    }
+}
 
-lazy_static! {
-    pub static ref AGENTS_INIT: [Agent; NUM_AGENTS] = {
-        let mut agents_vec = Vec::with_capacity(NUM_AGENTS);
+impl Agent {
+    /// The starting population, its first `--predators` agents predators (or with
+    /// `--particle-life` its agents dealt evenly between the species) and its first
+    /// `--organisms` bodies' worth of agents laid out as organisms, followed by free slots up to
+    /// the agent capacity
+    pub fn init_population(config: &Config) -> Vec<Agent> {
         let mut rng = thread_rng();
         let position_range = Uniform::from(200..=800);
         let angle_range = Uniform::from(0.0..std::f32::consts::TAU);
 
-        for _ in 0..NUM_AGENTS {
-            let x = position_range.sample(&mut rng) as f32;
-            let y = position_range.sample(&mut rng) as f32;
-            // println!("{}, {}", x, y);
-            agents_vec.push(Agent {
-                position: [x, y],
-                angle: angle_range.sample(&mut rng),
-                turn_speed: 0f32,
+        let organism_cells = organisms::organism_cells(config);
+        let mut agents = Vec::with_capacity(config.agent_capacity() as usize);
+        for i in 0..config.agents {
+            let (position, angle) = match organism_cells.get(i as usize) {
+                Some(&cell) => cell,
+                None => (
+                    [
+                        position_range.sample(&mut rng) as f32,
+                        position_range.sample(&mut rng) as f32,
+                    ],
+                    angle_range.sample(&mut rng),
+                ),
+            };
+            agents.push(Agent {
+                position,
+                angle,
+                energy: config.initial_energy,
+                alive: 1,
+                sensor_spread: config.sensor_spread,
+                sensor_distance: config.sensor_distance,
+                speed: config.speed,
+                turn_strength: config.turn_strength,
+                species: if config.particle_life {
+                    i % config.species
+                } else if i < config.predators {
+                    SPECIES_PREDATOR
+                } else {
+                    SPECIES_PREY
+                },
+                ..Default::default()
             })
         }
+        agents.resize(config.agent_capacity() as usize, Agent::default());
+        agents
+    }
 
-        agents_vec.try_into().unwrap()
-    };
-}
+    pub fn is_alive(&self) -> bool {
+        self.alive != 0
+    }
 
-impl Agent {
-    pub fn buf_init_desc() -> wgpu::util::BufferInitDescriptor<'static> {
+    pub fn buf_init_desc(agents: &[Agent]) -> wgpu::util::BufferInitDescriptor<'_> {
         wgpu::util::BufferInitDescriptor {
             label: Some("Agent Buffer"),
-            contents: bytemuck::cast_slice(&*AGENTS_INIT),
+            contents: bytemuck::cast_slice(agents),
             usage: wgpu::BufferUsages::VERTEX
                 | wgpu::BufferUsages::STORAGE
                 | wgpu::BufferUsages::COPY_SRC
@@ -47,6 +121,38 @@ impl Agent {
         }
     }
 
+    /// Contents of the lifecycle buffer for a population: the free-slot count and the births
+    /// count, then a stack of free slot indices and this step's parents, each `agents.len()`
+    /// long. Must match `Lifecycle` in the agent shader.
+    pub fn lifecycle_buf_contents(agents: &[Agent]) -> Vec<u32> {
+        let free: Vec<u32> = (0..agents.len() as u32)
+            .filter(|&i| !agents[i as usize].is_alive())
+            .collect();
+        let mut contents = vec![free.len() as u32, 0];
+        contents.extend(&free);
+        contents.resize(2 + 2 * agents.len(), 0);
+        contents
+    }
+
+    pub fn lifecycle_buf_init_desc(contents: &[u32]) -> wgpu::util::BufferInitDescriptor<'_> {
+        wgpu::util::BufferInitDescriptor {
+            label: Some("Agent Lifecycle Buffer"),
+            contents: bytemuck::cast_slice(contents),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        }
+    }
+
+    /// Per cell, one plus the index of a prey agent in it this step, or zero. Written by the
+    /// agent pass, claimed by predators in the hunt pass and cleared every step.
+    pub fn occupancy_buf_desc(width: usize, height: usize) -> wgpu::BufferDescriptor<'static> {
+        wgpu::BufferDescriptor {
+            label: Some("Prey Occupancy Buffer"),
+            size: (width * height * std::mem::size_of::<u32>()) as u64,
+            mapped_at_creation: false,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        }
+    }
+
     pub fn compute_shader_desc() -> wgpu::ShaderModuleDescriptor<'static> {
         wgpu::ShaderModuleDescriptor {
             label: Some("Agent Compute Shader"),
@@ -102,7 +208,7 @@ impl Agent {
                     },
                     count: None,
                 },
-                // Destination Env Buffer: the compute shader leaves a trail on this
+                // Deposit Buffer: the compute shader accumulates its trail into this
                 wgpu::BindGroupLayoutEntry {
                     binding: 4,
                     visibility: wgpu::ShaderStages::COMPUTE,
@@ -113,6 +219,61 @@ impl Agent {
                     },
                     count: None,
                 },
+                // Obstacle Mask: the compute shader treats masked cells as walls
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                // Lifecycle Buffer: free slots and births, shared by both halves of the step
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Prey Occupancy Buffer: prey mark their cells, predators claim them
+                wgpu::BindGroupLayoutEntry {
+                    binding: 7,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Hash Cell Buffer: where each grid cell's agents are in the sorted buffer
+                wgpu::BindGroupLayoutEntry {
+                    binding: 8,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Hash Sorted Agent Buffer: source buffer indices ordered by grid cell
+                wgpu::BindGroupLayoutEntry {
+                    binding: 9,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
         }
     }
@@ -0,0 +1,596 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use crate::config::Config;
+use crate::environment::EnvCell;
+
+/// A junction (3+ branches) or end point of the skeleton, in world cells
+#[derive(Clone, Debug)]
+pub struct SkeletonNode {
+    pub x: f32,
+    pub y: f32,
+    pub degree: usize,
+}
+
+/// A skeleton branch between two nodes
+#[derive(Clone, Debug)]
+pub struct SkeletonEdge {
+    pub a: usize,
+    pub b: usize,
+    pub length: f32,
+    pub mean_intensity: f32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SkeletonGraph {
+    pub nodes: Vec<SkeletonNode>,
+    pub edges: Vec<SkeletonEdge>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GraphStats {
+    pub node_count: usize,
+    pub edge_count: usize,
+    pub mean_degree: f32,
+    pub total_length: f32,
+    pub mean_intensity: f32,
+}
+
+/// 8-neighbourhood in clockwise order starting north, as Zhang-Suen expects
+const NEIGHBOURS: [(i32, i32); 8] = [
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+];
+
+/// Cells whose pheromone level is at least `threshold`
+pub fn threshold(field: &[EnvCell], threshold: f32) -> Vec<bool> {
+    field
+        .iter()
+        .map(|c| c.pheromone_level >= threshold)
+        .collect()
+}
+
+/// Thins a binary image to one-cell-wide lines (Zhang-Suen)
+pub fn skeletonize(mask: &[bool], width: u32, height: u32) -> Vec<bool> {
+    let (w, h) = (width as i32, height as i32);
+    let mut img = mask.to_vec();
+    let at = |img: &[bool], x: i32, y: i32| -> bool {
+        x >= 0 && y >= 0 && x < w && y < h && img[(y * w + x) as usize]
+    };
+    loop {
+        let mut changed = false;
+        for step in 0..2 {
+            let mut remove = Vec::new();
+            for y in 0..h {
+                for x in 0..w {
+                    if !img[(y * w + x) as usize] {
+                        continue;
+                    }
+                    let p = NEIGHBOURS.map(|(dx, dy)| at(&img, x + dx, y + dy));
+                    let filled = p.iter().filter(|&&v| v).count();
+                    let transitions = (0..8).filter(|&i| !p[i] && p[(i + 1) % 8]).count();
+                    // p[0] = north, p[2] = east, p[4] = south, p[6] = west
+                    let (a, b) = if step == 0 {
+                        (p[0] && p[2] && p[4], p[2] && p[4] && p[6])
+                    } else {
+                        (p[0] && p[2] && p[6], p[0] && p[4] && p[6])
+                    };
+                    if (2..=6).contains(&filled) && transitions == 1 && !a && !b {
+                        remove.push((y * w + x) as usize);
+                    }
+                }
+            }
+            changed |= !remove.is_empty();
+            for i in remove {
+                img[i] = false;
+            }
+        }
+        if !changed {
+            remove_staircases(&mut img, w, h);
+            return img;
+        }
+    }
+}
+
+/// Zhang-Suen leaves an extra cell at each step of a staircase, which would have three
+/// neighbours and read as a junction. Removes, in place, every cell with two 4-neighbours at a
+/// right angle (and no others) whose neighbours stay connected without it. A cell with three or
+/// four 4-neighbours is the centre of a junction and is always kept.
+fn remove_staircases(img: &mut [bool], w: i32, h: i32) {
+    for y in 0..h {
+        for x in 0..w {
+            if !img[(y * w + x) as usize] {
+                continue;
+            }
+            let p = NEIGHBOURS.map(|(dx, dy)| {
+                let (nx, ny) = (x + dx, y + dy);
+                nx >= 0 && ny >= 0 && nx < w && ny < h && img[(ny * w + nx) as usize]
+            });
+            // Even entries are the 4-neighbours
+            let sides = (0..8).step_by(2).filter(|&i| p[i]).count();
+            let corner = sides == 2 && (0..8).step_by(2).any(|i| p[i] && p[(i + 2) % 8]);
+            if corner && neighbour_groups(&p) == 1 {
+                img[(y * w + x) as usize] = false;
+            }
+        }
+    }
+}
+
+/// Groups the set cells of an 8-neighbourhood fall into when connected to each other but not
+/// through the centre. Consecutive cells touch, as do two 4-neighbours with one cell between.
+fn neighbour_groups(p: &[bool; 8]) -> usize {
+    let mut group = [usize::MAX; 8];
+    let mut groups = 0;
+    for start in 0..8 {
+        if !p[start] || group[start] != usize::MAX {
+            continue;
+        }
+        let mut stack = vec![start];
+        group[start] = groups;
+        while let Some(i) = stack.pop() {
+            let mut touching = vec![(i + 1) % 8, (i + 7) % 8];
+            if i % 2 == 0 {
+                touching.extend([(i + 2) % 8, (i + 6) % 8]);
+            }
+            for j in touching {
+                if p[j] && group[j] == usize::MAX {
+                    group[j] = groups;
+                    stack.push(j);
+                }
+            }
+        }
+        groups += 1;
+    }
+    groups
+}
+
+impl SkeletonGraph {
+    /// Builds the junction/end-point graph of a skeleton. Touching node cells are merged into a
+    /// single node, and every run of two-neighbour cells between nodes becomes an edge.
+    /// Closed loops without any junction are not represented.
+    pub fn from_skeleton(skeleton: &[bool], field: &[EnvCell], width: u32, height: u32) -> Self {
+        Self::anchored(skeleton, &[], field, width, height)
+    }
+
+    /// As `from_skeleton`, but skeleton cells set in `anchors` are always node cells, so a
+    /// branch passing through an anchored area is split there
+    pub fn anchored(
+        skeleton: &[bool],
+        anchors: &[bool],
+        field: &[EnvCell],
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let (w, h) = (width as i32, height as i32);
+        let index = |x: i32, y: i32| (y * w + x) as usize;
+        let neighbours = |x: i32, y: i32| {
+            NEIGHBOURS
+                .iter()
+                .map(move |(dx, dy)| (x + dx, y + dy))
+                .filter(|&(nx, ny)| {
+                    nx >= 0 && ny >= 0 && nx < w && ny < h && skeleton[index(nx, ny)]
+                })
+        };
+
+        let is_node = |x: i32, y: i32| {
+            anchors.get(index(x, y)).copied().unwrap_or(false) || neighbours(x, y).count() != 2
+        };
+
+        // Label node cells, flood-filling touching ones into the same node
+        let mut node_of = vec![usize::MAX; skeleton.len()];
+        let mut graph = SkeletonGraph::default();
+        for y in 0..h {
+            for x in 0..w {
+                if !skeleton[index(x, y)] || node_of[index(x, y)] != usize::MAX || !is_node(x, y) {
+                    continue;
+                }
+                let id = graph.nodes.len();
+                let mut stack = vec![(x, y)];
+                let mut cells = Vec::new();
+                node_of[index(x, y)] = id;
+                while let Some((cx, cy)) = stack.pop() {
+                    cells.push((cx, cy));
+                    for (nx, ny) in neighbours(cx, cy) {
+                        if node_of[index(nx, ny)] == usize::MAX && is_node(nx, ny) {
+                            node_of[index(nx, ny)] = id;
+                            stack.push((nx, ny));
+                        }
+                    }
+                }
+                let n = cells.len() as f32;
+                graph.nodes.push(SkeletonNode {
+                    x: cells.iter().map(|c| c.0 as f32).sum::<f32>() / n,
+                    y: cells.iter().map(|c| c.1 as f32).sum::<f32>() / n,
+                    degree: 0,
+                });
+            }
+        }
+
+        // Walk every branch leaving a node until it reaches another node
+        let mut visited = vec![false; skeleton.len()];
+        for y in 0..h {
+            for x in 0..w {
+                let start = node_of[index(x, y)];
+                if start == usize::MAX {
+                    continue;
+                }
+                for (nx, ny) in neighbours(x, y) {
+                    let i = index(nx, ny);
+                    if node_of[i] != usize::MAX || visited[i] {
+                        continue;
+                    }
+                    let (mut px, mut py) = (x, y);
+                    let (mut cx, mut cy) = (nx, ny);
+                    let mut length = step_length(px, py, cx, cy);
+                    let mut intensity = 0.0;
+                    let mut cells = 0;
+                    let end = loop {
+                        let ci = index(cx, cy);
+                        if node_of[ci] != usize::MAX {
+                            break Some(node_of[ci]);
+                        }
+                        visited[ci] = true;
+                        intensity += field[ci].pheromone_level;
+                        cells += 1;
+                        let next = neighbours(cx, cy).find(|&(qx, qy)| {
+                            (qx, qy) != (px, py)
+                                && (node_of[index(qx, qy)] != usize::MAX || !visited[index(qx, qy)])
+                        });
+                        match next {
+                            Some((qx, qy)) => {
+                                length += step_length(cx, cy, qx, qy);
+                                (px, py, cx, cy) = (cx, cy, qx, qy);
+                            }
+                            None => break None,
+                        }
+                    };
+                    if let Some(end) = end {
+                        graph.add_edge(start, end, length, intensity / cells.max(1) as f32);
+                    }
+                }
+            }
+        }
+
+        graph
+    }
+
+    fn add_edge(&mut self, a: usize, b: usize, length: f32, mean_intensity: f32) {
+        self.nodes[a].degree += 1;
+        self.nodes[b].degree += 1;
+        self.edges.push(SkeletonEdge {
+            a: a.min(b),
+            b: a.max(b),
+            length,
+            mean_intensity,
+        });
+    }
+
+    pub fn stats(&self) -> GraphStats {
+        let total_length: f32 = self.edges.iter().map(|e| e.length).sum();
+        GraphStats {
+            node_count: self.nodes.len(),
+            edge_count: self.edges.len(),
+            mean_degree: if self.nodes.is_empty() {
+                0.0
+            } else {
+                self.nodes.iter().map(|n| n.degree).sum::<usize>() as f32 / self.nodes.len() as f32
+            },
+            total_length,
+            mean_intensity: if total_length > 0.0 {
+                self.edges
+                    .iter()
+                    .map(|e| e.mean_intensity * e.length)
+                    .sum::<f32>()
+                    / total_length
+            } else {
+                0.0
+            },
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let nodes = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| {
+                format!(
+                    "{{\"id\": {}, \"x\": {}, \"y\": {}, \"degree\": {}}}",
+                    i, n.x, n.y, n.degree
+                )
+            })
+            .collect::<Vec<_>>();
+        let edges = self
+            .edges
+            .iter()
+            .map(|e| {
+                format!(
+                    "{{\"source\": {}, \"target\": {}, \"length\": {}, \"mean_intensity\": {}}}",
+                    e.a, e.b, e.length, e.mean_intensity
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{{\n  \"nodes\": [\n    {}\n  ],\n  \"edges\": [\n    {}\n  ]\n}}\n",
+            nodes.join(",\n    "),
+            edges.join(",\n    ")
+        )
+    }
+}
+
+fn step_length(x0: i32, y0: i32, x1: i32, y1: i32) -> f32 {
+    if x0 != x1 && y0 != y1 {
+        std::f32::consts::SQRT_2
+    } else {
+        1.0
+    }
+}
+
+/// Periodically skeletonises the field, appending graph statistics to `<out>.csv` and
+/// overwriting `<out>.json` with the latest graph
+pub struct SkeletonRecorder {
+    interval: u64,
+    threshold: f32,
+    out: PathBuf,
+    csv: BufWriter<File>,
+}
+
+impl SkeletonRecorder {
+    pub fn from_config(config: &Config) -> io::Result<Option<Self>> {
+        if config.skeleton_interval == 0 {
+            return Ok(None);
+        }
+        let mut csv = BufWriter::new(File::create(config.skeleton_out.with_extension("csv"))?);
+        writeln!(
+            csv,
+            "frame,node_count,edge_count,mean_degree,total_length,mean_intensity"
+        )?;
+        Ok(Some(Self {
+            interval: config.skeleton_interval,
+            threshold: config.skeleton_threshold,
+            out: config.skeleton_out.clone(),
+            csv,
+        }))
+    }
+
+    pub fn record(
+        &mut self,
+        frame: u64,
+        field: &[EnvCell],
+        width: u32,
+        height: u32,
+    ) -> io::Result<GraphStats> {
+        let skeleton = skeletonize(&threshold(field, self.threshold), width, height);
+        let graph = SkeletonGraph::from_skeleton(&skeleton, field, width, height);
+        let stats = graph.stats();
+        writeln!(
+            self.csv,
+            "{},{},{},{},{},{}",
+            frame,
+            stats.node_count,
+            stats.edge_count,
+            stats.mean_degree,
+            stats.total_length,
+            stats.mean_intensity
+        )?;
+        self.csv.flush()?;
+        std::fs::write(self.out.with_extension("json"), graph.to_json())?;
+        Ok(stats)
+    }
+
+    pub fn spawn(mut self, width: u32, height: u32) -> SkeletonWorker {
+        let interval = self.interval;
+        let busy = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel::<(u64, Vec<EnvCell>)>();
+        let thread = {
+            let busy = busy.clone();
+            std::thread::spawn(move || {
+                for (frame, field) in receiver {
+                    if let Err(e) = self.record(frame, &field, width, height) {
+                        eprintln!("ERROR: Could not write skeleton analysis: {}", e);
+                    }
+                    busy.store(false, Ordering::Release);
+                }
+            })
+        };
+        SkeletonWorker {
+            interval,
+            busy,
+            sender: Some(sender),
+            thread: Some(thread),
+        }
+    }
+}
+
+/// A `SkeletonRecorder` running on its own thread, so skeletonising a large field does not
+/// stall the frame. One field is analysed at a time.
+pub struct SkeletonWorker {
+    interval: u64,
+    busy: Arc<AtomicBool>,
+    sender: Option<Sender<(u64, Vec<EnvCell>)>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SkeletonWorker {
+    /// Whether a field should be sent at this frame. Frames that fall while the previous field
+    /// is still being analysed are skipped, so the field is not read back for nothing.
+    pub fn is_due(&self, frame: u64) -> bool {
+        frame.is_multiple_of(self.interval) && !self.busy.load(Ordering::Acquire)
+    }
+
+    pub fn send(&self, frame: u64, field: Vec<EnvCell>) {
+        if let Some(sender) = &self.sender {
+            self.busy.store(true, Ordering::Release);
+            // Only fails if the thread panicked, which has already been reported
+            let _ = sender.send((frame, field));
+        }
+    }
+}
+
+/// Waits for the analysis in progress, so its results are written before exiting
+impl Drop for SkeletonWorker {
+    fn drop(&mut self) {
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A mask from rows of `#` (set) and `.` (clear), with its width and height
+    fn grid(rows: &[&str]) -> (Vec<bool>, u32, u32) {
+        let mask = rows
+            .iter()
+            .flat_map(|row| row.chars().map(|c| c == '#'))
+            .collect();
+        (mask, rows[0].len() as u32, rows.len() as u32)
+    }
+
+    fn show(mask: &[bool], width: u32) -> Vec<String> {
+        mask.chunks(width as usize)
+            .map(|row| row.iter().map(|&set| if set { '#' } else { '.' }).collect())
+            .collect()
+    }
+
+    /// Node degrees of the graph of a skeleton, sorted
+    fn degrees(graph: &SkeletonGraph) -> Vec<usize> {
+        let mut degrees: Vec<usize> = graph.nodes.iter().map(|n| n.degree).collect();
+        degrees.sort();
+        degrees
+    }
+
+    fn empty_field(width: u32, height: u32) -> Vec<EnvCell> {
+        vec![bytemuck::Zeroable::zeroed(); (width * height) as usize]
+    }
+
+    #[test]
+    fn thick_plus_thins_to_one_junction() {
+        let (mask, w, h) = grid(&[
+            "...............",
+            "......###......",
+            "......###......",
+            "......###......",
+            "......###......",
+            "......###......",
+            ".#############.",
+            ".#############.",
+            ".#############.",
+            "......###......",
+            "......###......",
+            "......###......",
+            "......###......",
+            "......###......",
+            "...............",
+        ]);
+        let skeleton = skeletonize(&mask, w, h);
+        let graph = SkeletonGraph::from_skeleton(&skeleton, &empty_field(w, h), w, h);
+        assert_eq!(
+            degrees(&graph),
+            [1, 1, 1, 1, 4],
+            "{:#?}",
+            show(&skeleton, w)
+        );
+        assert_eq!(graph.edges.len(), 4);
+    }
+
+    #[test]
+    fn thick_diagonal_thins_to_one_branch() {
+        let (mask, w, h) = grid(&[
+            "..........",
+            ".##.......",
+            ".####.....",
+            "..#####...",
+            "....#####.",
+            "......###.",
+            "..........",
+        ]);
+        let skeleton = skeletonize(&mask, w, h);
+        let graph = SkeletonGraph::from_skeleton(&skeleton, &empty_field(w, h), w, h);
+        assert_eq!(degrees(&graph), [1, 1], "{:#?}", show(&skeleton, w));
+        assert_eq!(graph.edges.len(), 1);
+    }
+
+    #[test]
+    fn staircases_become_diagonals() {
+        let (mut img, w, h) = grid(&["#...", "##..", ".##.", "..##"]);
+        remove_staircases(&mut img, w as i32, h as i32);
+        assert_eq!(show(&img, w), ["#...", ".#..", "..#.", "...#"]);
+    }
+
+    #[test]
+    fn junction_centres_are_not_staircases() {
+        let rows = [".....", "..#..", "#####", "..#..", "....."];
+        let (mut img, w, h) = grid(&rows);
+        remove_staircases(&mut img, w as i32, h as i32);
+        assert_eq!(show(&img, w), rows);
+        let rows = ["..#..", "..#..", "#####"];
+        let (mut img, w, h) = grid(&rows);
+        remove_staircases(&mut img, w as i32, h as i32);
+        assert_eq!(show(&img, w), rows);
+    }
+
+    #[test]
+    fn neighbour_groups_connect_around_the_centre() {
+        let set = |cells: &[usize]| {
+            let mut p = [false; 8];
+            for &i in cells {
+                p[i] = true;
+            }
+            p
+        };
+        assert_eq!(neighbour_groups(&set(&[])), 0);
+        assert_eq!(neighbour_groups(&[true; 8]), 1);
+        // North and north-east touch
+        assert_eq!(neighbour_groups(&set(&[0, 1])), 1);
+        // North and east touch diagonally, around the empty north-east
+        assert_eq!(neighbour_groups(&set(&[0, 2])), 1);
+        // North-east and south-east do not, with east empty
+        assert_eq!(neighbour_groups(&set(&[1, 3])), 2);
+        assert_eq!(neighbour_groups(&set(&[0, 4])), 2);
+        assert_eq!(neighbour_groups(&set(&[1, 3, 5])), 3);
+    }
+
+    #[test]
+    fn anchors_split_branches() {
+        let (skeleton, w, h) = grid(&[
+            ".....#.....",
+            ".....#.....",
+            ".....#.....",
+            ".....#.....",
+            ".....#.....",
+            "###########",
+            ".....#.....",
+            ".....#.....",
+            ".....#.....",
+            ".....#.....",
+            ".....#.....",
+        ]);
+        let field = empty_field(w, h);
+        let graph = SkeletonGraph::from_skeleton(&skeleton, &field, w, h);
+        assert_eq!(degrees(&graph), [1, 1, 1, 1, 4]);
+
+        // A node in the middle of the left arm
+        let mut anchors = vec![false; skeleton.len()];
+        anchors[(5 * w + 2) as usize] = true;
+        let anchored = SkeletonGraph::anchored(&skeleton, &anchors, &field, w, h);
+        assert_eq!(degrees(&anchored), [1, 1, 1, 1, 2, 4]);
+        assert_eq!(anchored.edges.len(), 5);
+        let length = |g: &SkeletonGraph| g.edges.iter().map(|e| e.length).sum::<f32>();
+        assert_eq!(length(&anchored), length(&graph));
+    }
+}
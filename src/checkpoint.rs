@@ -0,0 +1,16 @@
+//! CPU-side copies of the simulation state, taken periodically so a lost GPU device can be
+//! replaced without restarting the run.
+
+use crate::agents::Agent;
+use crate::environment::EnvCell;
+
+/// The agent and env buffers as they stood once `frame` steps had run
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    pub frame: u64,
+    /// Size of the world in cells, which the env buffer is laid out for
+    pub width: u32,
+    pub height: u32,
+    pub agents: Vec<Agent>,
+    pub env: Vec<EnvCell>,
+}
@@ -0,0 +1,421 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use crate::adapter::{Backend, PowerPreference};
+use crate::agents::{SteeringRule, MAX_SENSORS};
+use crate::environment::{DiffusionKernel, MAX_LAPLACIAN_RATE};
+use crate::food::FoodSource;
+use crate::organisms::OrganismShape;
+use crate::particle_life::MAX_SPECIES;
+use crate::stats::{TRAIT_DISTANCE, TRAIT_RANGES, TRAIT_SPEED, TRAIT_SPREAD, TRAIT_TURN};
+
+/// Command line options for a simulation run
+#[derive(Parser, Debug, Clone)]
+#[command(about = "GPU slime mould / cell life simulation")]
+pub struct Config {
+    /// Black-and-white PNG marking obstacles: dark pixels become walls that agents
+    /// cannot enter and pheromone cannot diffuse through. Stretched to the world size.
+    #[arg(long, value_name = "PNG")]
+    pub mask: Option<PathBuf>,
+
+    /// Persistent food source, repeatable: `point:x,y[,rate]` or `disc:x,y,radius[,rate]`
+    /// in world cells. Sources emit `rate` into the food channel every step.
+    #[arg(long = "food", value_name = "SPEC")]
+    pub food_sources: Vec<FoodSource>,
+
+    /// Greyscale PNG of food emission, stretched to the world size
+    #[arg(long, value_name = "PNG")]
+    pub food_image: Option<PathBuf>,
+
+    /// Emission per step of white pixels in `--food-image`
+    #[arg(long, default_value_t = crate::food::DEFAULT_EMISSION_RATE)]
+    pub food_image_rate: f32,
+
+    /// How strongly agents steer towards food relative to the pheromone trail
+    #[arg(long, default_value_t = 1.0)]
+    pub food_weight: f32,
+
+    /// Amount of food lost from every cell each step
+    #[arg(long, default_value_t = 0.002)]
+    pub food_decay: f32,
+
+    /// Pheromone each agent deposits per step; deposits on the same cell add up
+    #[arg(long, default_value_t = 1.0)]
+    pub deposit: f32,
+
+    /// Agents at the start of the run
+    #[arg(long, default_value_t = 10_000)]
+    pub agents: u32,
+
+    /// Agent slots on the GPU; births with no free slot are dropped. Defaults to `--agents`,
+    /// or twice that with `--lifecycle`
+    #[arg(long, value_name = "N")]
+    pub agent_capacity: Option<u32>,
+
+    /// Give agents energy and age: moving costs energy, food restores it, agents die when it
+    /// runs out (or at `--max-age`) and split in two above `--split-energy`
+    #[arg(long)]
+    pub lifecycle: bool,
+
+    /// Energy each agent starts with
+    #[arg(long, default_value_t = 1.0)]
+    pub initial_energy: f32,
+
+    /// Energy spent per step of movement
+    #[arg(long, default_value_t = 0.01)]
+    pub move_cost: f32,
+
+    /// Energy gained per step per unit of food in the agent's cell
+    #[arg(long, default_value_t = 0.05)]
+    pub food_energy: f32,
+
+    /// Energy at which an agent splits into two offspring sharing it
+    #[arg(long, default_value_t = 2.0)]
+    pub split_energy: f32,
+
+    /// Steps an agent lives at most (0 = no limit)
+    #[arg(long, default_value_t = 0)]
+    pub max_age: u32,
+
+    /// Largest relative change to each trait (sensor spread and distance, speed, turn
+    /// strength) between a parent and the sibling it splits off
+    #[arg(long, default_value_t = 0.05)]
+    pub mutation: f32,
+
+    /// How many of the starting agents are predators. Predators follow the trail prey lay,
+    /// eat prey they reach and leave a scent that prey steer away from; use with
+    /// `--lifecycle` for populations that rise and fall
+    #[arg(long, default_value_t = 0)]
+    pub predators: u32,
+
+    /// Energy a predator gains from each prey it eats
+    #[arg(long, default_value_t = 1.0)]
+    pub predator_gain: f32,
+
+    /// How strongly prey avoid predator scent, relative to the trail they follow
+    #[arg(long, default_value_t = 1.0)]
+    pub flee_weight: f32,
+
+    /// Distance, in cells, within which agents see each other. Also the cell size of the
+    /// spatial hash agents are sorted into to find their neighbours.
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..))]
+    pub neighbour_radius: u32,
+
+    /// Fraction of the way an agent turns each step towards heading away from its neighbours,
+    /// scaled down when they are few or far (0 = off)
+    #[arg(long, default_value_t = 0.0)]
+    pub avoidance: f32,
+
+    /// Fraction of each turn taken from the flocking rules rather than the pheromone sensors:
+    /// 0 follows trails only, 1 flocks only
+    #[arg(long, default_value_t = 0.0)]
+    pub flocking: f32,
+
+    /// Flocking weight of steering away from neighbours closer than `--neighbour-radius`
+    #[arg(long, default_value_t = 1.5)]
+    pub separation: f32,
+
+    /// Flocking weight of steering towards the neighbours' mean heading
+    #[arg(long, default_value_t = 1.0)]
+    pub alignment: f32,
+
+    /// Flocking weight of steering towards the neighbours' centre
+    #[arg(long, default_value_t = 1.0)]
+    pub cohesion: f32,
+
+    /// Replace trail following with particle life: each agent belongs to one of `--species`
+    /// species, is pulled or pushed by the agents within the radius of each species pair and
+    /// moves by velocity and friction. While running, the arrow keys select a pair of the
+    /// interaction matrix, +/- change its attraction, [/] its radius and R randomises it.
+    #[arg(long, conflicts_with = "predators")]
+    pub particle_life: bool,
+
+    /// Number of particle life species
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..=MAX_SPECIES as i64))]
+    pub species: u32,
+
+    /// CSV of particle life interactions, `from,to,attraction[,radius]` rows of species indices,
+    /// attraction in [-1, 1] and radius in cells (at most `--neighbour-radius`, the default).
+    /// Unlisted pairs have no attraction. Defaults to a random matrix.
+    #[arg(long, value_name = "CSV")]
+    pub interaction_matrix: Option<PathBuf>,
+
+    /// Fraction of its velocity a particle loses each step
+    #[arg(long, default_value_t = 0.2)]
+    pub friction: f32,
+
+    /// Velocity a particle gains per step from a full-strength interaction
+    #[arg(long, default_value_t = 0.05)]
+    pub interaction_force: f32,
+
+    /// Bond the first agents into this many organisms of `--organism-cells` cells joined by
+    /// springs. The cells still sense, steer and deposit trail, while the springs hold each
+    /// body together, align its cells' headings and pulse to swim it along. Agents keep their
+    /// slots for the whole run, so this does not combine with `--lifecycle`.
+    #[arg(long, default_value_t = 0, conflicts_with = "lifecycle")]
+    pub organisms: u32,
+
+    /// Cells in each organism
+    #[arg(long, default_value_t = 16, value_parser = clap::value_parser!(u32).range(3..))]
+    pub organism_cells: u32,
+
+    /// Body plan of the organisms
+    #[arg(long, value_enum, default_value_t = OrganismShape::Ring)]
+    pub organism_shape: OrganismShape,
+
+    /// Rest length of the springs between neighbouring cells of an organism, in cells
+    #[arg(long, default_value_t = 3.0)]
+    pub bond_length: f32,
+
+    /// Fraction of each spring's stretch corrected per constraint iteration, from 0 to 1
+    #[arg(long, default_value_t = 0.5, value_parser = parse_fraction)]
+    pub bond_stiffness: f32,
+
+    /// Constraint iterations per step; more keep the bodies stiffer
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..))]
+    pub bond_iterations: u32,
+
+    /// Steps per organism pulse (0 = no pulsing)
+    #[arg(long, default_value_t = 60)]
+    pub pulse_period: u32,
+
+    /// Fraction by which the springs shorten at the peak of a pulse, from 0 to 1
+    #[arg(long, default_value_t = 0.3, value_parser = parse_fraction)]
+    pub pulse_amplitude: f32,
+
+    /// Cells an organism's cells are pushed forward per step at the height of a contraction.
+    /// The push is set directly rather than arising from the springs against the field, so
+    /// the pulse only times the swimming.
+    #[arg(long, default_value_t = 1.0)]
+    pub pulse_thrust: f32,
+
+    /// Number of sensors, spread evenly across the sensor arc
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..=MAX_SENSORS as i64))]
+    pub sensor_count: u32,
+
+    /// Angle from straight ahead to the outermost sensors, in radians. Each agent carries its
+    /// own copy, starting from this value and mutating with `--lifecycle`.
+    #[arg(long, default_value_t = 1.0, value_parser = parse_trait::<TRAIT_SPREAD>)]
+    pub sensor_spread: f32,
+
+    /// Distance from an agent to its sensors, in cells. Per agent like `--sensor-spread`.
+    #[arg(long, default_value_t = 6.0, value_parser = parse_trait::<TRAIT_DISTANCE>)]
+    pub sensor_distance: f32,
+
+    /// Each sensor sums a square of (2 * radius + 1)^2 cells around its point
+    #[arg(long, default_value_t = 2)]
+    pub sensor_radius: u32,
+
+    /// How agents turn in response to their sensors
+    #[arg(long, value_enum, default_value_t = SteeringRule::Discrete)]
+    pub steering: SteeringRule,
+
+    /// Fraction of the chosen sensor angle an agent turns by each step. Per agent like
+    /// `--sensor-spread`.
+    #[arg(long, default_value_t = 0.1, value_parser = parse_trait::<TRAIT_TURN>)]
+    pub turn_strength: f32,
+
+    /// Cells an agent moves per step. Per agent like `--sensor-spread`; with `--lifecycle`
+    /// moving faster costs proportionally more energy.
+    #[arg(long, default_value_t = 1.0, value_parser = parse_trait::<TRAIT_SPEED>)]
+    pub speed: f32,
+
+    /// Pick randomly between sensors that tie for the strongest reading, instead of
+    /// preferring the one closest to straight ahead (then the right-hand one)
+    #[arg(long)]
+    pub random_ties: bool,
+
+    /// Sample the field at sensor points by bilinear interpolation instead of truncating them
+    /// to a cell
+    #[arg(long)]
+    pub bilinear_sensing: bool,
+
+    /// Splat each deposit across the four cells around the agent instead of the one it is in
+    #[arg(long)]
+    pub bilinear_deposit: bool,
+
+    /// Keep the env and agent layers in 8-bit textures even if float storage textures are
+    /// supported. Levels above 1.0 then clip.
+    #[arg(long)]
+    pub ldr_textures: bool,
+
+    /// Brightness of the tone-mapped trail when the layers are float textures
+    #[arg(long, default_value_t = 2.0)]
+    pub exposure: f32,
+
+    /// Diffusion model for pheromone and food
+    #[arg(long, value_enum, default_value_t = DiffusionKernel::Box5)]
+    pub diffusion: DiffusionKernel,
+
+    /// Fraction of the blurred value blended into each cell per step, from 0 to 1, or the
+    /// diffusion coefficient of the Laplacian kernel (stable up to 0.25)
+    #[arg(long, default_value_t = 0.1, value_parser = parse_fraction)]
+    pub diffusion_rate: f32,
+
+    /// Standard deviation of the Gaussian kernel, in cells
+    #[arg(long, default_value_t = 1.0, value_parser = parse_positive)]
+    pub sigma: f32,
+
+    /// Diffuse by reading every neighbour from the env buffer instead of through the
+    /// workgroup-shared tile
+    #[arg(long)]
+    pub naive_diffusion: bool,
+
+    /// Time this many env passes with the naive and the tiled kernel, print the results and
+    /// exit (0 to run the simulation)
+    #[arg(long, default_value_t = 0)]
+    pub benchmark_diffusion: u32,
+
+    /// CSV of transport-network nodes (`x,y` or `name,x,y` rows). Runs the Physarum network
+    /// scenario: food discs at every node, then the trail network is extracted and exported.
+    #[arg(long, value_name = "CSV")]
+    pub network_nodes: Option<PathBuf>,
+
+    /// Steps to run the network scenario before extracting the network
+    #[arg(long, default_value_t = 3000)]
+    pub network_steps: u64,
+
+    /// Radius in cells of the food disc placed at each network node
+    #[arg(long, default_value_t = 6.0)]
+    pub network_node_radius: f32,
+
+    /// Trail level above which a cell belongs to the network before it is skeletonised
+    #[arg(long, default_value_t = 0.05)]
+    pub network_threshold: f32,
+
+    /// Output path stem for the network scenario; `.graphml` and `.json` are appended
+    #[arg(long, value_name = "PATH", default_value = "network")]
+    pub network_out: PathBuf,
+
+    /// Skeletonise the trail field every N steps and record its graph statistics (0 = off).
+    /// The analysis runs in the background, and a step falling while it is busy is skipped.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub skeleton_interval: u64,
+
+    /// Trail level above which a cell belongs to the network before skeletonisation
+    #[arg(long, default_value_t = 0.1)]
+    pub skeleton_threshold: f32,
+
+    /// Output path stem for skeleton analysis: statistics over time go to `.csv`, the
+    /// latest junction/edge graph to `.json`
+    #[arg(long, value_name = "PATH", default_value = "skeleton")]
+    pub skeleton_out: PathBuf,
+
+    /// Write per-step GPU statistics (pheromone total/mean/max and histogram, mean turn
+    /// speed, heading histogram, occupied cell fraction) to this CSV file
+    #[arg(long, value_name = "CSV")]
+    pub stats_csv: Option<PathBuf>,
+
+    /// Show the latest per-step statistics in the window title
+    #[arg(long)]
+    pub stats_title: bool,
+
+    /// Watch the WGSL files in the source tree and rebuild pipelines when they change,
+    /// keeping the simulation running. Compile errors are printed and the previous shader
+    /// stays in use.
+    #[arg(long)]
+    pub hot_reload: bool,
+
+    /// Check the structs declared in the WGSL files against the Rust structs uploaded into
+    /// them, print the result and exit
+    #[arg(long)]
+    pub check_layouts: bool,
+
+    /// Print the graphics adapters found on the selected backends, with whether each has the
+    /// features and limits the simulation needs, and exit
+    #[arg(long)]
+    pub list_adapters: bool,
+
+    /// Graphics API to run on
+    #[arg(long, value_enum, default_value_t = Backend::All)]
+    pub backend: Backend,
+
+    /// Which kind of adapter to prefer when no `--adapter` is given
+    #[arg(long, value_enum, default_value_t = PowerPreference::None)]
+    pub power_preference: PowerPreference,
+
+    /// Run on the first adapter whose name contains this (case-insensitive), as shown by
+    /// `--list-adapters`
+    #[arg(long, value_name = "NAME")]
+    pub adapter: Option<String>,
+
+    /// Copy the simulation state back to the CPU every N steps, so it can resume from there if
+    /// the GPU device is lost (0 = off; a lost device then restarts the simulation)
+    #[arg(long, value_name = "N", default_value_t = 1000)]
+    pub checkpoint_interval: u64,
+
+    /// Only use a fallback (software) adapter, such as a CPU rasteriser on CI runners
+    #[arg(long)]
+    pub fallback_adapter: bool,
+
+    /// Time the compute and render passes with GPU timestamp queries (CPU frame time when
+    /// unsupported) and print rolling averages
+    #[arg(long)]
+    pub profile: bool,
+
+    /// Show the profiling averages in the window title; implies --profile
+    #[arg(long)]
+    pub profile_title: bool,
+}
+
+/// Parses the starting value of a heritable trait, one of the `TRAIT_*` indices into
+/// `TRAIT_RANGES`, which must lie within the range mutation clamps the trait to
+fn parse_trait<const TRAIT: usize>(s: &str) -> Result<f32, String> {
+    let (_, max) = TRAIT_RANGES[TRAIT];
+    let value: f32 = s.parse().map_err(|e| format!("{e}"))?;
+    if (0.0..=max).contains(&value) {
+        Ok(value)
+    } else {
+        Err(format!("{value} is not in 0..={max}"))
+    }
+}
+
+/// Parses a fraction, which must lie in 0..=1
+fn parse_fraction(s: &str) -> Result<f32, String> {
+    let value: f32 = s.parse().map_err(|e| format!("{e}"))?;
+    if (0.0..=1.0).contains(&value) {
+        Ok(value)
+    } else {
+        Err(format!("{value} is not in 0..=1"))
+    }
+}
+
+/// Parses a finite value above zero
+fn parse_positive(s: &str) -> Result<f32, String> {
+    let value: f32 = s.parse().map_err(|e| format!("{e}"))?;
+    if value > 0.0 && value.is_finite() {
+        Ok(value)
+    } else {
+        Err(format!("{value} is not a finite value above 0"))
+    }
+}
+
+impl Config {
+    /// Number of agent slots, never fewer than the starting population
+    pub fn agent_capacity(&self) -> u32 {
+        let default = if self.lifecycle {
+            2 * self.agents
+        } else {
+            self.agents
+        };
+        self.agent_capacity.unwrap_or(default).max(self.agents)
+    }
+
+    /// Rejects combinations of arguments that are each valid alone
+    pub fn validate(&self) -> Result<(), String> {
+        if self.diffusion == DiffusionKernel::Laplacian && self.diffusion_rate > MAX_LAPLACIAN_RATE
+        {
+            return Err(format!(
+                "--diffusion-rate {} is unstable with the Laplacian kernel, which needs 0..={}",
+                self.diffusion_rate, MAX_LAPLACIAN_RATE
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether any agent behaviour looks up neighbours, so the spatial hash is rebuilt each step
+    pub fn spatial_hash(&self) -> bool {
+        self.avoidance > 0.0 || self.flocking > 0.0 || self.particle_life
+    }
+}
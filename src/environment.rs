@@ -1,5 +1,42 @@
-pub struct EnvCell {
-    pheromone_level: f32,
+use crate::layout::shader_struct;
+
+/// Largest diffusion coefficient with which the explicit Laplacian step stays stable
+pub const MAX_LAPLACIAN_RATE: f32 = 0.25;
+
+/// How pheromone and food spread between cells each step
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffusionKernel {
+    /// Mean of the 3x3 neighbourhood
+    Box3,
+    /// Mean of the 5x5 neighbourhood
+    Box5,
+    /// Separable Gaussian blur, run as a row pass and a column pass
+    Gaussian,
+    /// Discrete 4-neighbour Laplacian (explicit heat equation step)
+    Laplacian,
+}
+
+impl DiffusionKernel {
+    /// Value of the env shader's `kernel` uniform, must match the `KERNEL_*` constants
+    pub fn shader_id(self) -> u32 {
+        match self {
+            Self::Box3 => 0,
+            Self::Box5 => 1,
+            Self::Gaussian => 2,
+            Self::Laplacian => 3,
+        }
+    }
+}
+
+shader_struct! {
+    #[repr(C)]
+    #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, Default)]
+    pub struct EnvCell {
+        pub pheromone_level: f32,
+        pub food_level: f32,
+        // Left by predators, avoided by prey
+        pub scent_level: f32,
+    }
 }
 
 impl EnvCell {
@@ -15,6 +52,29 @@ impl EnvCell {
         }
     }
 
+    /// Two fixed-point `u32`s per cell (scaled by `DEPOSIT_SCALE` in the shaders) that agents
+    /// atomically add their deposits to: all of the prey trail, then all of the predator
+    /// scent. The env pass merges it into the field, and it is cleared after every step.
+    pub fn deposit_buf_desc(width: usize, height: usize) -> wgpu::BufferDescriptor<'static> {
+        wgpu::BufferDescriptor {
+            label: Some("Deposit Buffer"),
+            size: (2 * width * height * std::mem::size_of::<u32>()) as u64,
+            mapped_at_creation: false,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        }
+    }
+
+    /// Holds the row-blurred field between the two passes of the Gaussian kernel, as
+    /// (pheromone, food, scent, unused)
+    pub fn blur_scratch_buf_desc(width: usize, height: usize) -> wgpu::BufferDescriptor<'static> {
+        wgpu::BufferDescriptor {
+            label: Some("Blur Scratch Buffer"),
+            size: (width * height * std::mem::size_of::<[f32; 4]>()) as u64,
+            mapped_at_creation: false,
+            usage: wgpu::BufferUsages::STORAGE,
+        }
+    }
+
     pub fn compute_shader_desc() -> wgpu::ShaderModuleDescriptor<'static> {
         wgpu::ShaderModuleDescriptor {
             label: Some("Env Compute Shader"),
@@ -59,6 +119,50 @@ impl EnvCell {
                     },
                     count: None,
                 },
+                // Obstacle Mask: pheromone does not diffuse through masked cells
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                // Food Emission: added to the food channel every step
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                // Deposit Buffer: this step's agent deposits, merged into the source field
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Blur Scratch Buffer: written by the Gaussian row pass, read by the column pass
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
         }
     }
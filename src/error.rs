@@ -0,0 +1,123 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Why `State::new` could not set up the simulation
+#[derive(Debug)]
+pub enum InitError {
+    /// The window has no area to render into, e.g. it started minimised
+    ZeroSizedWindow,
+    CreateSurface(wgpu::CreateSurfaceError),
+    NoAdapter,
+    /// No adapter named like `--adapter` can present to the window
+    AdapterNotFound {
+        name: String,
+        available: Vec<String>,
+    },
+    /// The adapter lacks features the simulation cannot run without
+    MissingFeatures {
+        adapter: String,
+        missing: wgpu::Features,
+    },
+    /// The adapter's limits are below what the world size and bind group layouts need
+    MissingLimits {
+        adapter: String,
+        missing: Vec<String>,
+    },
+    RequestDevice(wgpu::RequestDeviceError),
+    /// The surface reports no formats it can be configured with for the adapter
+    IncompatibleSurface {
+        adapter: String,
+    },
+    /// Structs declared in the WGSL files differ from the Rust structs uploaded into them
+    LayoutMismatch(Vec<String>),
+    /// A shader, bind group layout or pipeline failed validation
+    Validation(String),
+    ObstacleMask {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    FoodImage {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    InteractionMatrix {
+        path: PathBuf,
+        source: io::Error,
+    },
+    ShaderWatcher(notify::Error),
+}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroSizedWindow => {
+                write!(f, "the window has zero size; un-minimise it and retry")
+            }
+            Self::CreateSurface(e) => write!(
+                f,
+                "could not create a surface for the window: {e}. Check that a display server \
+                 is running and the graphics drivers are installed"
+            ),
+            Self::NoAdapter => write!(
+                f,
+                "no graphics adapter can present to the window. Run with --list-adapters to \
+                 see the adapters found, or try --backend gl or --fallback-adapter"
+            ),
+            Self::AdapterNotFound { name, available } => write!(
+                f,
+                "no adapter matching \"{name}\" can present to the window; found: {}",
+                available.join(", ")
+            ),
+            Self::MissingFeatures { adapter, missing } => write!(
+                f,
+                "adapter {adapter} lacks required features {missing:?}. Update its drivers or \
+                 run on another adapter (see --list-adapters)"
+            ),
+            Self::MissingLimits { adapter, missing } => write!(
+                f,
+                "adapter {adapter} is below the limits needed for this world:\n  {}\nUse a \
+                 smaller window or another adapter (see --list-adapters)",
+                missing.join("\n  ")
+            ),
+            Self::RequestDevice(e) => write!(f, "could not open the graphics device: {e}"),
+            Self::IncompatibleSurface { adapter } => write!(
+                f,
+                "adapter {adapter} supports no formats for the window surface"
+            ),
+            Self::LayoutMismatch(errors) => write!(
+                f,
+                "shader structs do not match the Rust structs:\n{}",
+                errors.join("\n")
+            ),
+            Self::Validation(e) => write!(f, "GPU validation failed:\n{e}"),
+            Self::ObstacleMask { path, source } => write!(
+                f,
+                "could not load obstacle mask {}: {source}",
+                path.display()
+            ),
+            Self::FoodImage { path, source } => {
+                write!(f, "could not load food image {}: {source}", path.display())
+            }
+            Self::InteractionMatrix { path, source } => write!(
+                f,
+                "could not load interaction matrix {}: {source}",
+                path.display()
+            ),
+            Self::ShaderWatcher(e) => write!(f, "could not watch shaders for changes: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for InitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::CreateSurface(e) => Some(e),
+            Self::RequestDevice(e) => Some(e),
+            Self::ObstacleMask { source, .. } | Self::FoodImage { source, .. } => Some(source),
+            Self::InteractionMatrix { source, .. } => Some(source),
+            Self::ShaderWatcher(e) => Some(e),
+            _ => None,
+        }
+    }
+}
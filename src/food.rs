@@ -0,0 +1,177 @@
+use std::path::Path;
+use std::str::FromStr;
+
+/// A persistent source that emits into the food channel every step
+#[derive(Clone, Debug, PartialEq)]
+pub enum FoodSource {
+    /// A single cell: `point:x,y[,rate]`
+    Point { x: u32, y: u32, rate: f32 },
+    /// A filled circle: `disc:x,y,radius[,rate]`
+    Disc {
+        x: u32,
+        y: u32,
+        radius: f32,
+        rate: f32,
+    },
+}
+
+pub const DEFAULT_EMISSION_RATE: f32 = 0.05;
+
+impl FromStr for FoodSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, args) = s.split_once(':').ok_or_else(|| {
+            format!("expected `point:x,y[,rate]` or `disc:x,y,radius[,rate]`, got `{s}`")
+        })?;
+        let nums = args
+            .split(',')
+            .map(|n| n.trim().parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("invalid number in `{s}`: {e}"))?;
+        match (kind, nums.as_slice()) {
+            ("point", [x, y]) => Ok(Self::Point {
+                x: coordinate(*x, s)?,
+                y: coordinate(*y, s)?,
+                rate: DEFAULT_EMISSION_RATE,
+            }),
+            ("point", [x, y, rate]) => Ok(Self::Point {
+                x: coordinate(*x, s)?,
+                y: coordinate(*y, s)?,
+                rate: *rate,
+            }),
+            ("disc", [x, y, radius]) => Ok(Self::Disc {
+                x: coordinate(*x, s)?,
+                y: coordinate(*y, s)?,
+                radius: *radius,
+                rate: DEFAULT_EMISSION_RATE,
+            }),
+            ("disc", [x, y, radius, rate]) => Ok(Self::Disc {
+                x: coordinate(*x, s)?,
+                y: coordinate(*y, s)?,
+                radius: *radius,
+                rate: *rate,
+            }),
+            _ => Err(format!(
+                "expected `point:x,y[,rate]` or `disc:x,y,radius[,rate]`, got `{s}`"
+            )),
+        }
+    }
+}
+
+/// A cell coordinate of a food source. Negative and non-finite values would saturate to an
+/// edge of the world when cast, so they are rejected instead.
+fn coordinate(value: f32, s: &str) -> Result<u32, String> {
+    if value >= 0.0 && value.is_finite() {
+        Ok(value as u32)
+    } else {
+        Err(format!("invalid coordinate {value} in `{s}`"))
+    }
+}
+
+/// Per-cell emission rate of the food channel
+pub struct FoodField {
+    width: u32,
+    height: u32,
+    emission: Vec<f32>,
+}
+
+impl FoodField {
+    /// A field with no sources
+    pub fn empty(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            emission: vec![0.0; (width * height) as usize],
+        }
+    }
+
+    pub fn add_source(&mut self, source: &FoodSource) {
+        match *source {
+            FoodSource::Point { x, y, rate } => self.emit(x, y, rate),
+            FoodSource::Disc { x, y, radius, rate } => {
+                let r = radius.ceil() as i64;
+                for dy in -r..=r {
+                    for dx in -r..=r {
+                        if ((dx * dx + dy * dy) as f32) > radius * radius {
+                            continue;
+                        }
+                        let (cx, cy) = (x as i64 + dx, y as i64 + dy);
+                        if cx >= 0 && cy >= 0 {
+                            self.emit(cx as u32, cy as u32, rate);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Adds an image as a source, stretched to the world size.
+    /// Pixel brightness scales `rate`, so white pixels emit `rate` per step.
+    pub fn add_image(&mut self, path: &Path, rate: f32) -> Result<(), image::ImageError> {
+        let img = image::open(path)?.into_luma8();
+        // Image rows go top to bottom, world rows go bottom to top
+        for y in 0..self.height {
+            let img_y =
+                ((self.height - 1 - y) as u64 * img.height() as u64 / self.height as u64) as u32;
+            for x in 0..self.width {
+                let img_x = (x as u64 * img.width() as u64 / self.width as u64) as u32;
+                let luma = img.get_pixel(img_x, img_y).0[0];
+                self.emit(x, y, rate * luma as f32 / 255.0);
+            }
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, x: u32, y: u32, rate: f32) {
+        if x < self.width && y < self.height {
+            self.emission[(y * self.width + x) as usize] += rate;
+        }
+    }
+
+    pub fn texture_desc(&self) -> wgpu::TextureDescriptor<'static> {
+        wgpu::TextureDescriptor {
+            label: Some("Food Emission Texture"),
+            size: wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        }
+    }
+
+    /// Row-major texel data matching `texture_desc`
+    pub fn data(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.emission)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sources_parse_with_optional_rates() {
+        let point: FoodSource = "point:10.7,20".parse().unwrap();
+        assert!(
+            matches!(point, FoodSource::Point { x: 10, y: 20, rate } if rate == DEFAULT_EMISSION_RATE)
+        );
+        let disc: FoodSource = "disc: 5, 6, 3.5, 0.2".parse().unwrap();
+        assert!(
+            matches!(disc, FoodSource::Disc { x: 5, y: 6, radius, rate } if radius == 3.5 && rate == 0.2)
+        );
+    }
+
+    #[test]
+    fn negative_and_non_finite_coordinates_are_rejected() {
+        for s in ["point:-1,5", "point:5,-0.5", "disc:NaN,5,2", "disc:5,inf,2"] {
+            assert!(s.parse::<FoodSource>().is_err(), "{}", s);
+        }
+    }
+}
@@ -1,10 +1,41 @@
-use wgpu::util::DeviceExt;
-use winit::{dpi::PhysicalSize, event::WindowEvent, window::Window};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
 
-use crate::agents::{Agent, NUM_AGENTS};
-use crate::environment::EnvCell;
-use crate::params::{AgentComputeParams, EnvComputeParams, Params};
+use wgpu::util::DeviceExt;
+use winit::{
+    dpi::PhysicalSize,
+    event::{ElementState, WindowEvent},
+    window::Window,
+};
+
+use crate::adapter::{self, REQUIRED_FEATURES};
+use crate::agents::{Agent, BIRTH_COUNT_OFFSET};
+use crate::checkpoint::Checkpoint;
+use crate::config::Config;
+use crate::environment::{DiffusionKernel, EnvCell};
+use crate::error::InitError;
+use crate::food::FoodField;
+use crate::hdr;
+use crate::hot_reload::{ShaderFile, ShaderWatcher};
+use crate::layout;
+use crate::obstacles::ObstacleMask;
+use crate::organisms::{self, Bond};
+use crate::params::{
+    AgentComputeParams, BondComputeParams, EnvComputeParams, HashComputeParams, Params,
+};
+use crate::particle_life::ParticleLife;
+use crate::profiler::{ProfiledPass, Profiler};
+use crate::readback::ReadbackRing;
 use crate::render_plane::{Vertex, PLANE_VERTICES};
+use crate::spatial_hash::HashGrid;
+use crate::stats::{FrameStats, GpuStats, StatsRecorder};
+use crate::step::{PairBinding, StepBuffers};
+
+/// Number of stats samples that can be in flight between the GPU and the CPU
+const STATS_READBACK_SLOTS: usize = 3;
+/// Frames between profiling summaries printed to the terminal
+const PROFILE_PRINT_INTERVAL: u64 = 120;
 
 pub struct State<'a> {
     gpu_surface: wgpu::Surface<'a>,
@@ -22,64 +53,198 @@ pub struct State<'a> {
     pipeline_plane_agents: wgpu::RenderPipeline,
 
     pipeline_compute_agents: wgpu::ComputePipeline,
-    pipeline_compute_env: wgpu::ComputePipeline,
+    // Places offspring in free slots, only dispatched with the lifecycle enabled
+    pipeline_spawn_agents: wgpu::ComputePipeline,
+    // Lets predators eat prey, only dispatched when there are predators
+    pipeline_hunt_agents: wgpu::ComputePipeline,
+    // Dispatched instead of the agent compute pipeline with `--particle-life`
+    pipeline_particle_life: wgpu::ComputePipeline,
+    // Deposits and draws the organism cells after the bond pass, only dispatched with organisms
+    pipeline_settle_agents: wgpu::ComputePipeline,
+    pipeline_compute_env_naive: wgpu::ComputePipeline,
+    pipeline_compute_env_tiled: wgpu::ComputePipeline,
+    tiled_diffusion: bool,
+    // First pass of the Gaussian kernel, only dispatched when it is selected
+    pipeline_blur_rows: wgpu::ComputePipeline,
+    diffusion: DiffusionKernel,
 
     buf_plane_env: wgpu::Buffer,
     buf_plane_agents: wgpu::Buffer,
 
-    _buf_agent_forward: wgpu::Buffer,
-    _buf_agent_reverse: wgpu::Buffer,
+    // [forward, reverse], indexed as described in `step`
+    buf_agents: [wgpu::Buffer; 2],
+    buf_lifecycle: wgpu::Buffer,
+    buf_occupancy: wgpu::Buffer,
+    agent_capacity: u32,
+    lifecycle: bool,
+    predators: bool,
+    // The interaction matrix as edited at runtime, when running particle life
+    particle_life: Option<ParticleLife>,
+    buf_interactions: wgpu::Buffer,
+
+    // Only rebuilt each step when an agent behaviour looks up neighbours
+    spatial_hash: bool,
+    hash_grid: HashGrid,
+    buf_hash_cells: wgpu::Buffer,
+    _buf_hash_block_sums: wgpu::Buffer,
+    _buf_hash_ranks: wgpu::Buffer,
+    _buf_hash_sorted: wgpu::Buffer,
+    // Indexed like the agent compute bindgroups, sorting the agents that step reads
+    bindgroup_hash: [wgpu::BindGroup; 2],
+    // The hash passes in dispatch order
+    pipelines_hash: [wgpu::ComputePipeline; 5],
+
+    // Agents in organisms, the first slots; the bond passes only run when there are any
+    organism_cells: u32,
+    bond_iterations: u32,
+    _buf_bonds: wgpu::Buffer,
+    _buf_bond_staging: wgpu::Buffer,
+    // Indexed like the agent compute bindgroups, reshaping the agents that step writes
+    bindgroup_bonds: [wgpu::BindGroup; 2],
+    // The pulse, solve, apply and draw passes
+    pipelines_bonds: [wgpu::ComputePipeline; 4],
+
+    // [forward, reverse], indexed as described in `step`
+    buf_env: [wgpu::Buffer; 2],
+    buf_deposits: wgpu::Buffer,
+    _buf_blur_scratch: wgpu::Buffer,
 
     _texture_env: wgpu::Texture,
     _texture_agents: wgpu::Texture,
+    _texture_obstacles: wgpu::Texture,
+    _texture_food: wgpu::Texture,
+
+    // Stats are only gathered when a recorder is configured
+    stats_recorder: Option<StatsRecorder>,
+    // Indexed like the compute bindgroups: bindgroup i reads what the frame with parity i wrote
+    bindgroup_stats: [wgpu::BindGroup; 2],
+    pipeline_stats_env: wgpu::ComputePipeline,
+    pipeline_stats_agents: wgpu::ComputePipeline,
+
+    // Kept to rebuild pipelines when their shaders are edited
+    shader_watcher: Option<ShaderWatcher>,
+    pipeline_layout_plane: wgpu::PipelineLayout,
+    pipeline_layout_agents: wgpu::PipelineLayout,
+    pipeline_layout_env: wgpu::PipelineLayout,
+    pipeline_layout_stats: wgpu::PipelineLayout,
+    pipeline_layout_hash: wgpu::PipelineLayout,
+    pipeline_layout_bonds: wgpu::PipelineLayout,
+    sim_format: wgpu::TextureFormat,
+    buf_stats: wgpu::Buffer,
+    stats_readback: ReadbackRing,
+
+    profiler: Option<Profiler>,
+    profile_in_title: bool,
 
     window_handle: &'a Window,
+    // Last title set on the window, so it is only set again when it changes
+    title: String,
     pub window_size: PhysicalSize<u32>,
-
-    uniforms: Params,
-    uniform_buf_agent_compute: wgpu::Buffer,
-    uniform_buf_env_compute: wgpu::Buffer,
+    // Size of the simulated world, fixed at startup
+    world_size: PhysicalSize<u32>,
+
+    _uniforms: Params,
+    _uniform_buf_agent_compute: wgpu::Buffer,
+    _uniform_buf_env_compute: wgpu::Buffer,
+    _uniform_buf_agent_render: wgpu::Buffer,
+    _uniform_buf_env_render: wgpu::Buffer,
+    _uniform_buf_hash_compute: wgpu::Buffer,
+    uniform_buf_bond_compute: wgpu::Buffer,
     uniform_bindgroup_agent_compute: wgpu::BindGroup,
     uniform_bindgroup_env_compute: wgpu::BindGroup,
+    uniform_bindgroup_hash_compute: wgpu::BindGroup,
+    uniform_bindgroup_bond_compute: wgpu::BindGroup,
 
     frame_num: u64,
+
+    // Set by the device lost callback; the owner then replaces the whole `State`
+    device_lost: Arc<AtomicBool>,
+    checkpoint_interval: u64,
+    checkpoint: Option<Checkpoint>,
+}
+
+/// What outlives a `State` whose device was lost, handed over to the `State` replacing it
+pub struct LostState {
+    /// The latest checkpoint, taken every `--checkpoint-interval` steps
+    checkpoint: Option<Checkpoint>,
+    stats_recorder: Option<StatsRecorder>,
+    /// The interaction matrix as edited since startup
+    particle_life: Option<ParticleLife>,
 }
 
 impl<'a> State<'a> {
-    pub async fn new(window: &'a Window) -> Option<Self> {
-        let size = window.inner_size();
-        if size.height == 0 || size.width == 0 {
-            return None;
+    /// `size` is the size of the world in cells, which stays fixed while the window and its
+    /// surface may be resized
+    pub async fn new(
+        window: &'a Window,
+        size: PhysicalSize<u32>,
+        sim_config: &Config,
+        stats_recorder: Option<StatsRecorder>,
+    ) -> Result<Self, InitError> {
+        let window_size = window.inner_size();
+        if window_size.height == 0 || window_size.width == 0 {
+            return Err(InitError::ZeroSizedWindow);
         }
-
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
-            ..Default::default()
-        });
-
-        let surface = instance.create_surface(window).ok()?;
-
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await?;
+        layout::check_layouts().map_err(InitError::LayoutMismatch)?;
+
+        let instance = adapter::create_instance(sim_config);
+
+        let surface = instance
+            .create_surface(window)
+            .map_err(InitError::CreateSurface)?;
+
+        let adapter = adapter::select_adapter(&instance, &surface, sim_config).await?;
+        let adapter_info = adapter.get_info();
+        let adapter_name = adapter_info.name;
+        println!(
+            "Using adapter {} ({:?}, {:?})",
+            adapter_name, adapter_info.backend, adapter_info.device_type
+        );
+
+        let missing = REQUIRED_FEATURES - adapter.features();
+        if !missing.is_empty() {
+            return Err(InitError::MissingFeatures {
+                adapter: adapter_name,
+                missing,
+            });
+        }
+        let required_limits =
+            adapter::required_limits(size.width, size.height, sim_config.agent_capacity());
+        let missing = adapter::missing_limits(&adapter, &required_limits);
+        if !missing.is_empty() {
+            return Err(InitError::MissingLimits {
+                adapter: adapter_name,
+                missing,
+            });
+        }
+        // Take the adapter's texture size limits so the window can grow beyond the world
+        let required_limits = required_limits.using_resolution(adapter.limits());
+
+        let profiling = sim_config.profile || sim_config.profile_title;
+        let profiler_features = if profiling {
+            Profiler::features(adapter.features())
+        } else {
+            wgpu::Features::empty()
+        };
 
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
-                    required_features: wgpu::Features::CLEAR_TEXTURE,
-                    required_limits: wgpu::Limits::default(),
+                    required_features: REQUIRED_FEATURES | profiler_features,
+                    required_limits,
                     label: None,
                 },
                 None,
             )
             .await
-            .ok()?;
+            .map_err(InitError::RequestDevice)?;
 
         let surface_caps = surface.get_capabilities(&adapter);
+        if surface_caps.formats.is_empty() {
+            return Err(InitError::IncompatibleSurface {
+                adapter: adapter_name,
+            });
+        }
 
         let surface_format = surface_caps
             .formats
@@ -91,8 +256,8 @@ impl<'a> State<'a> {
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
-            width: size.width,
-            height: size.height,
+            width: window_size.width,
+            height: window_size.height,
             present_mode: wgpu::PresentMode::Fifo,
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
@@ -101,6 +266,19 @@ impl<'a> State<'a> {
 
         surface.configure(&device, &config);
 
+        let device_lost = Arc::new(AtomicBool::new(false));
+        let device_lost_cb = device_lost.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            // Dropping the device on purpose, e.g. when replacing a lost one, also lands here
+            if !matches!(reason, wgpu::DeviceLostReason::Dropped) {
+                eprintln!("ERROR: GPU device lost ({:?}): {}", reason, message);
+                device_lost_cb.store(true, Ordering::Release);
+            }
+        });
+
+        // Shader and pipeline validation errors would otherwise panic in the default handler
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+
         let buf_env_vertices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Plane Vertices Buffer"),
             contents: bytemuck::cast_slice(PLANE_VERTICES),
@@ -113,7 +291,8 @@ impl<'a> State<'a> {
             usage: wgpu::BufferUsages::VERTEX,
         });
 
-        let uniforms = Params::new(size.width, size.height);
+        let sim_format = hdr::sim_texture_format(&adapter, sim_config);
+        let uniforms = Params::new(size.width, size.height, sim_config, hdr::is_hdr(sim_format));
         let uniform_agent_compute = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Agent Compute Uniform"),
             contents: bytemuck::cast_slice(&[uniforms.agent_compute_params]),
@@ -121,6 +300,27 @@ impl<'a> State<'a> {
         });
         let uniform_agent_compute_bindgroup_layout =
             device.create_bind_group_layout(&AgentComputeParams::bind_layout_desc());
+        let max_radius = sim_config.neighbour_radius as f32;
+        let particle_life = match &sim_config.interaction_matrix {
+            _ if !sim_config.particle_life => None,
+            Some(path) => Some(
+                ParticleLife::load(path, sim_config.species, max_radius).map_err(|source| {
+                    InitError::InteractionMatrix {
+                        path: path.clone(),
+                        source,
+                    }
+                })?,
+            ),
+            None => Some(ParticleLife::new(sim_config.species, max_radius)),
+        };
+        // Bound even when unused, so the agent shader has a single layout
+        let interactions = particle_life
+            .as_ref()
+            .map_or(bytemuck::Zeroable::zeroed(), |p| p.matrix);
+        let buf_interactions = device.create_buffer_init(&interactions.buf_init_desc());
+        if let Some(particle_life) = &particle_life {
+            print!("{}", particle_life);
+        }
 
         let uniform_agent_render = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Agent Render Uniform"),
@@ -142,14 +342,36 @@ impl<'a> State<'a> {
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
+        let uniform_hash_compute = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Hash Compute Uniform"),
+            contents: bytemuck::cast_slice(&[uniforms.hash_compute_params]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let uniform_hash_compute_bindgroup_layout =
+            device.create_bind_group_layout(&HashComputeParams::bind_layout_desc());
+
+        let uniform_bond_compute = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Bond Compute Uniform"),
+            contents: bytemuck::cast_slice(&[uniforms.bond_compute_params]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let uniform_bond_compute_bindgroup_layout =
+            device.create_bind_group_layout(&BondComputeParams::bind_layout_desc());
+
         let uniform_agent_compute_bindgroup =
             device.create_bind_group(&wgpu::BindGroupDescriptor {
                 label: Some("Agent Compute Uniform Bind Group"),
                 layout: &uniform_agent_compute_bindgroup_layout,
-                entries: &[wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: uniform_agent_compute.as_entire_binding(),
-                }],
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: uniform_agent_compute.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: buf_interactions.as_entire_binding(),
+                    },
+                ],
             });
 
         let uniform_env_compute_bindgroup = device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -161,6 +383,24 @@ impl<'a> State<'a> {
             }],
         });
 
+        let uniform_hash_compute_bindgroup = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Hash Compute Uniform Bind Group"),
+            layout: &uniform_hash_compute_bindgroup_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_hash_compute.as_entire_binding(),
+            }],
+        });
+
+        let uniform_bond_compute_bindgroup = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Bond Compute Uniform Bind Group"),
+            layout: &uniform_bond_compute_bindgroup_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_bond_compute.as_entire_binding(),
+            }],
+        });
+
         let texture_agents = device.create_texture(&wgpu::TextureDescriptor {
             label: Some("Agent Texture"),
             size: wgpu::Extent3d {
@@ -171,7 +411,7 @@ impl<'a> State<'a> {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
+            format: sim_format,
             usage: wgpu::TextureUsages::STORAGE_BINDING
                 | wgpu::TextureUsages::COPY_DST
                 | wgpu::TextureUsages::TEXTURE_BINDING,
@@ -200,7 +440,7 @@ impl<'a> State<'a> {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
+            format: sim_format,
             usage: wgpu::TextureUsages::STORAGE_BINDING
                 | wgpu::TextureUsages::COPY_DST
                 | wgpu::TextureUsages::TEXTURE_BINDING,
@@ -218,6 +458,47 @@ impl<'a> State<'a> {
             ..Default::default()
         });
 
+        let obstacle_mask = match &sim_config.mask {
+            Some(path) => match ObstacleMask::from_image(path, size.width, size.height) {
+                Ok(mask) => mask,
+                Err(source) => {
+                    return Err(InitError::ObstacleMask {
+                        path: path.clone(),
+                        source,
+                    })
+                }
+            },
+            None => ObstacleMask::empty(size.width, size.height),
+        };
+        let texture_obstacles = device.create_texture_with_data(
+            &queue,
+            &obstacle_mask.texture_desc(),
+            wgpu::util::TextureDataOrder::LayerMajor,
+            obstacle_mask.data(),
+        );
+        let texture_obstacles_view =
+            texture_obstacles.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut food_field = FoodField::empty(size.width, size.height);
+        for source in &sim_config.food_sources {
+            food_field.add_source(source);
+        }
+        if let Some(path) = &sim_config.food_image {
+            food_field
+                .add_image(path, sim_config.food_image_rate)
+                .map_err(|source| InitError::FoodImage {
+                    path: path.clone(),
+                    source,
+                })?;
+        }
+        let texture_food = device.create_texture_with_data(
+            &queue,
+            &food_field.texture_desc(),
+            wgpu::util::TextureDataOrder::LayerMajor,
+            food_field.data(),
+        );
+        let texture_food_view = texture_food.create_view(&wgpu::TextureViewDescriptor::default());
+
         // Modules common to both planes
         let plane_bindgroup_layout = device.create_bind_group_layout(&Vertex::bind_layout_desc());
         let plane_pipeline_layout =
@@ -241,42 +522,19 @@ impl<'a> State<'a> {
                     binding: 1,
                     resource: wgpu::BindingResource::Sampler(&texture_env_sampler),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_env_render.as_entire_binding(),
+                },
             ],
         });
-        let plane_env_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Env Plane Render Pipeline"),
-            layout: Some(&plane_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &plane_env_shader,
-                entry_point: "vs_main",
-                buffers: &[Vertex::buf_desc()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &plane_env_shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            depth_stencil: None,
-            multiview: None,
-        });
+        let plane_env_pipeline = create_plane_pipeline(
+            &device,
+            &plane_pipeline_layout,
+            &plane_env_shader,
+            config.format,
+            "Env Plane Render Pipeline",
+        );
 
         let plane_agent_shader = device.create_shader_module(Vertex::shader_agent_desc());
         let plane_agent_bindgroup = device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -291,45 +549,36 @@ impl<'a> State<'a> {
                     binding: 1,
                     resource: wgpu::BindingResource::Sampler(&texture_agents_sampler),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_agent_render.as_entire_binding(),
+                },
             ],
         });
-        let plane_agent_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Agent Plane Render Pipeline"),
-            layout: Some(&plane_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &plane_agent_shader,
-                entry_point: "vs_main",
-                buffers: &[Vertex::buf_desc()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &plane_agent_shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            depth_stencil: None,
-            multiview: None,
-        });
-
-        let buf_agent_forward = device.create_buffer_init(&Agent::buf_init_desc());
-        let buf_agent_reverse = device.create_buffer_init(&Agent::buf_init_desc());
+        let plane_agent_pipeline = create_plane_pipeline(
+            &device,
+            &plane_pipeline_layout,
+            &plane_agent_shader,
+            config.format,
+            "Agent Plane Render Pipeline",
+        );
+
+        let agents = Agent::init_population(sim_config);
+        let buf_agent_forward = device.create_buffer_init(&Agent::buf_init_desc(&agents));
+        let buf_agent_reverse = device.create_buffer_init(&Agent::buf_init_desc(&agents));
+        let buf_lifecycle = device.create_buffer_init(&Agent::lifecycle_buf_init_desc(
+            &Agent::lifecycle_buf_contents(&agents),
+        ));
+        let buf_occupancy = device.create_buffer(&Agent::occupancy_buf_desc(
+            size.width as usize,
+            size.height as usize,
+        ));
+        let hash_grid = HashGrid::new(size.width, size.height, sim_config.neighbour_radius);
+        let agent_capacity = sim_config.agent_capacity();
+        let buf_hash_cells = device.create_buffer(&hash_grid.cells_buf_desc());
+        let buf_hash_block_sums = device.create_buffer(&hash_grid.block_sums_buf_desc());
+        let buf_hash_ranks = device.create_buffer(&HashGrid::ranks_buf_desc(agent_capacity));
+        let buf_hash_sorted = device.create_buffer(&HashGrid::sorted_buf_desc(agent_capacity));
         let buf_env_forward = device.create_buffer(&EnvCell::buf_init_desc(
             size.width as usize,
             size.height as usize,
@@ -338,22 +587,44 @@ impl<'a> State<'a> {
             size.width as usize,
             size.height as usize,
         ));
+        let buf_deposits = device.create_buffer(&EnvCell::deposit_buf_desc(
+            size.width as usize,
+            size.height as usize,
+        ));
+        let buf_blur_scratch = device.create_buffer(&EnvCell::blur_scratch_buf_desc(
+            size.width as usize,
+            size.height as usize,
+        ));
 
-        let compute_agent_shader = device.create_shader_module(Agent::compute_shader_desc());
+        let compute_agent_shader = device.create_shader_module(hdr::shader_with_format(
+            Agent::compute_shader_desc(),
+            sim_format,
+        ));
         let compute_agent_bindgroup_layout =
-            device.create_bind_group_layout(&Agent::bind_layout_desc());
-        let compute_agent_bindgroups = [
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Agent::bind_layout_desc().label,
+                entries: &hdr::layout_entries_with_format(&Agent::bind_layout_desc(), sim_format),
+            });
+        let agent_pair = [&buf_agent_forward, &buf_agent_reverse];
+        let env_pair = [&buf_env_forward, &buf_env_reverse];
+        let compute_agent_bindgroups = [0, 1].map(|index| {
+            let bound = PairBinding::ReadToWrite.bound(index);
             device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Agent Compute Bindgroup Forward"),
+                label: Some(
+                    [
+                        "Agent Compute Bindgroup Forward",
+                        "Agent Compute Bindgroup Reverse",
+                    ][index],
+                ),
                 layout: &compute_agent_bindgroup_layout,
                 entries: &[
                     wgpu::BindGroupEntry {
                         binding: 0,
-                        resource: buf_agent_forward.as_entire_binding(),
+                        resource: agent_pair[bound.read].as_entire_binding(),
                     },
                     wgpu::BindGroupEntry {
                         binding: 1,
-                        resource: buf_agent_reverse.as_entire_binding(),
+                        resource: agent_pair[bound.write].as_entire_binding(),
                     },
                     wgpu::BindGroupEntry {
                         binding: 2,
@@ -361,41 +632,35 @@ impl<'a> State<'a> {
                     },
                     wgpu::BindGroupEntry {
                         binding: 3,
-                        resource: buf_env_reverse.as_entire_binding(),
+                        resource: env_pair[bound.read].as_entire_binding(),
                     },
                     wgpu::BindGroupEntry {
                         binding: 4,
-                        resource: buf_env_forward.as_entire_binding(),
+                        resource: buf_deposits.as_entire_binding(),
                     },
-                ],
-            }),
-            device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Agent Compute Bindgroup Reverse"),
-                layout: &compute_agent_bindgroup_layout,
-                entries: &[
                     wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: buf_agent_reverse.as_entire_binding(),
+                        binding: 5,
+                        resource: wgpu::BindingResource::TextureView(&texture_obstacles_view),
                     },
                     wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: buf_agent_forward.as_entire_binding(),
+                        binding: 6,
+                        resource: buf_lifecycle.as_entire_binding(),
                     },
                     wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: wgpu::BindingResource::TextureView(&texture_agents_view),
+                        binding: 7,
+                        resource: buf_occupancy.as_entire_binding(),
                     },
                     wgpu::BindGroupEntry {
-                        binding: 3,
-                        resource: buf_env_forward.as_entire_binding(),
+                        binding: 8,
+                        resource: buf_hash_cells.as_entire_binding(),
                     },
                     wgpu::BindGroupEntry {
-                        binding: 4,
-                        resource: buf_env_reverse.as_entire_binding(),
+                        binding: 9,
+                        resource: buf_hash_sorted.as_entire_binding(),
                     },
                 ],
-            }),
-        ];
+            })
+        });
         let compute_agent_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Agent Compute Pipeline Layout"),
@@ -405,55 +670,157 @@ impl<'a> State<'a> {
                 ],
                 push_constant_ranges: &[],
             });
-        let compute_agent_pipeline =
-            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-                label: Some("Agent Compute Pipeline"),
-                layout: Some(&compute_agent_pipeline_layout),
-                module: &compute_agent_shader,
-                entry_point: "compute_main",
-            });
+        let [compute_agent_pipeline, spawn_agent_pipeline, hunt_agent_pipeline, particle_life_pipeline, settle_agent_pipeline] =
+            create_agent_pipelines(
+                &device,
+                &compute_agent_pipeline_layout,
+                &compute_agent_shader,
+            );
 
-        let compute_env_shader = device.create_shader_module(EnvCell::compute_shader_desc());
-        let compute_env_bindgroup_layout =
-            device.create_bind_group_layout(&EnvCell::bind_layout_desc());
-        let compute_env_bindgroups = [
+        let hash_shader = device.create_shader_module(HashGrid::compute_shader_desc());
+        let hash_bindgroup_layout = device.create_bind_group_layout(&HashGrid::bind_layout_desc());
+        let hash_bindgroups = [0, 1].map(|index| {
+            let bound = PairBinding::ReadToWrite.bound(index);
             device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Env Compute Bindgroup Forward"),
-                layout: &compute_env_bindgroup_layout,
+                label: Some("Hash Compute Bindgroup"),
+                layout: &hash_bindgroup_layout,
                 entries: &[
                     wgpu::BindGroupEntry {
                         binding: 0,
-                        resource: buf_env_forward.as_entire_binding(),
+                        resource: agent_pair[bound.read].as_entire_binding(),
                     },
                     wgpu::BindGroupEntry {
                         binding: 1,
-                        resource: buf_env_reverse.as_entire_binding(),
+                        resource: buf_hash_cells.as_entire_binding(),
                     },
                     wgpu::BindGroupEntry {
                         binding: 2,
-                        resource: wgpu::BindingResource::TextureView(&texture_env_view),
-                    }
+                        resource: buf_hash_block_sums.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: buf_hash_ranks.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: buf_hash_sorted.as_entire_binding(),
+                    },
+                ],
+            })
+        });
+        let hash_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Hash Compute Pipeline Layout"),
+            bind_group_layouts: &[
+                &hash_bindgroup_layout,
+                &uniform_hash_compute_bindgroup_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+        let hash_pipelines = create_hash_pipelines(&device, &hash_pipeline_layout, &hash_shader);
+
+        let organism_cells = organisms::organism_cell_count(sim_config);
+        let buf_bonds =
+            device.create_buffer_init(&Bond::buf_init_desc(&Bond::buf_contents(sim_config)));
+        let buf_bond_staging = device.create_buffer(&Bond::staging_buf_desc(organism_cells));
+        let bond_shader = device.create_shader_module(hdr::shader_with_format(
+            Bond::compute_shader_desc(),
+            sim_format,
+        ));
+        let bond_bindgroup_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Bond::bind_layout_desc().label,
+                entries: &hdr::layout_entries_with_format(&Bond::bind_layout_desc(), sim_format),
+            });
+        let bond_bindgroups = [0, 1].map(|index| {
+            let bound = PairBinding::Written.bound(index);
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Bond Compute Bindgroup"),
+                layout: &bond_bindgroup_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: agent_pair[bound.write].as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: buf_bonds.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: buf_bond_staging.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::TextureView(&texture_agents_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: wgpu::BindingResource::TextureView(&texture_obstacles_view),
+                    },
                 ],
-            }),
+            })
+        });
+        let bond_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Bond Compute Pipeline Layout"),
+            bind_group_layouts: &[
+                &bond_bindgroup_layout,
+                &uniform_bond_compute_bindgroup_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+        let bond_pipelines = create_bond_pipelines(&device, &bond_pipeline_layout, &bond_shader);
+
+        let compute_env_shader = device.create_shader_module(hdr::shader_with_format(
+            EnvCell::compute_shader_desc(),
+            sim_format,
+        ));
+        let compute_env_bindgroup_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: EnvCell::bind_layout_desc().label,
+                entries: &hdr::layout_entries_with_format(&EnvCell::bind_layout_desc(), sim_format),
+            });
+        let compute_env_bindgroups = [0, 1].map(|index| {
+            let bound = PairBinding::ReadToWrite.bound(index);
             device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Env Compute Bindgroup Reverse"),
+                label: Some(
+                    [
+                        "Env Compute Bindgroup Forward",
+                        "Env Compute Bindgroup Reverse",
+                    ][index],
+                ),
                 layout: &compute_env_bindgroup_layout,
                 entries: &[
                     wgpu::BindGroupEntry {
                         binding: 0,
-                        resource: buf_env_reverse.as_entire_binding(),
+                        resource: env_pair[bound.read].as_entire_binding(),
                     },
                     wgpu::BindGroupEntry {
                         binding: 1,
-                        resource: buf_env_forward.as_entire_binding(),
+                        resource: env_pair[bound.write].as_entire_binding(),
                     },
                     wgpu::BindGroupEntry {
                         binding: 2,
                         resource: wgpu::BindingResource::TextureView(&texture_env_view),
-                    }
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::TextureView(&texture_obstacles_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: wgpu::BindingResource::TextureView(&texture_food_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 5,
+                        resource: buf_deposits.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 6,
+                        resource: buf_blur_scratch.as_entire_binding(),
+                    },
                 ],
-            }),
-        ];
+            })
+        });
         let compute_env_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Env Compute Pipeline Layout"),
@@ -463,15 +830,70 @@ impl<'a> State<'a> {
                 ],
                 push_constant_ranges: &[],
             });
-        let compute_env_pipeline =
-            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-                label: Some("Env Compute Pipeline"),
-                layout: Some(&compute_env_pipeline_layout),
-                module: &compute_env_shader,
-                entry_point: "compute_main",
+        let [compute_env_naive_pipeline, compute_env_tiled_pipeline, blur_rows_pipeline] =
+            create_env_pipelines(&device, &compute_env_pipeline_layout, &compute_env_shader);
+
+        let buf_stats = device.create_buffer(&GpuStats::buf_desc());
+        let stats_readback = ReadbackRing::new(
+            &device,
+            "Stats Readback Buffer",
+            buf_stats.size(),
+            STATS_READBACK_SLOTS,
+        );
+        let stats_shader = device.create_shader_module(GpuStats::compute_shader_desc());
+        let stats_bindgroup_layout = device.create_bind_group_layout(&GpuStats::bind_layout_desc());
+        let stats_bindgroups = [0, 1].map(|index| {
+            let bound = PairBinding::Written.bound(index);
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(
+                    [
+                        "Stats Compute Bindgroup Forward",
+                        "Stats Compute Bindgroup Reverse",
+                    ][index],
+                ),
+                layout: &stats_bindgroup_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: env_pair[bound.read].as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: agent_pair[bound.read].as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&texture_agents_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: buf_stats.as_entire_binding(),
+                    },
+                ],
+            })
+        });
+        let stats_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Stats Compute Pipeline Layout"),
+                bind_group_layouts: &[&stats_bindgroup_layout],
+                push_constant_ranges: &[],
             });
+        let [stats_env_pipeline, stats_agent_pipeline] =
+            create_stats_pipelines(&device, &stats_pipeline_layout, &stats_shader);
 
-        Some(Self {
+        let profiler = profiling.then(|| Profiler::new(&device, &queue));
+
+        if let Some(error) = device.pop_error_scope().await {
+            return Err(InitError::Validation(error.to_string()));
+        }
+
+        let shader_watcher = if sim_config.hot_reload {
+            Some(ShaderWatcher::new().map_err(InitError::ShaderWatcher)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
             gpu_surface: surface,
             gpu_device: device,
             gpu_queue: queue,
@@ -487,32 +909,93 @@ impl<'a> State<'a> {
             pipeline_plane_agents: plane_agent_pipeline,
 
             pipeline_compute_agents: compute_agent_pipeline,
-            pipeline_compute_env: compute_env_pipeline,
+            pipeline_spawn_agents: spawn_agent_pipeline,
+            pipeline_hunt_agents: hunt_agent_pipeline,
+            pipeline_particle_life: particle_life_pipeline,
+            pipeline_settle_agents: settle_agent_pipeline,
+            pipeline_compute_env_naive: compute_env_naive_pipeline,
+            pipeline_compute_env_tiled: compute_env_tiled_pipeline,
+            tiled_diffusion: !sim_config.naive_diffusion,
+            pipeline_blur_rows: blur_rows_pipeline,
+            diffusion: sim_config.diffusion,
 
             buf_plane_env: buf_env_vertices,
             buf_plane_agents: buf_agent_vertices,
 
             _texture_env: texture_env,
             _texture_agents: texture_agents,
-
-            _buf_agent_forward: buf_agent_forward,
-            _buf_agent_reverse: buf_agent_reverse,
+            _texture_obstacles: texture_obstacles,
+            _texture_food: texture_food,
+
+            buf_agents: [buf_agent_forward, buf_agent_reverse],
+            buf_lifecycle,
+            agent_capacity,
+            buf_occupancy,
+            spatial_hash: sim_config.spatial_hash(),
+            hash_grid,
+            buf_hash_cells,
+            _buf_hash_block_sums: buf_hash_block_sums,
+            _buf_hash_ranks: buf_hash_ranks,
+            _buf_hash_sorted: buf_hash_sorted,
+            bindgroup_hash: hash_bindgroups,
+            pipelines_hash: hash_pipelines,
+            organism_cells,
+            bond_iterations: sim_config.bond_iterations,
+            _buf_bonds: buf_bonds,
+            _buf_bond_staging: buf_bond_staging,
+            bindgroup_bonds: bond_bindgroups,
+            pipelines_bonds: bond_pipelines,
+            lifecycle: sim_config.lifecycle,
+            predators: sim_config.predators > 0,
+            particle_life,
+            buf_interactions,
+
+            buf_env: [buf_env_forward, buf_env_reverse],
+            buf_deposits,
+            _buf_blur_scratch: buf_blur_scratch,
+
+            stats_recorder,
+            bindgroup_stats: stats_bindgroups,
+            pipeline_stats_env: stats_env_pipeline,
+            pipeline_stats_agents: stats_agent_pipeline,
+
+            shader_watcher,
+            pipeline_layout_plane: plane_pipeline_layout,
+            pipeline_layout_agents: compute_agent_pipeline_layout,
+            pipeline_layout_env: compute_env_pipeline_layout,
+            pipeline_layout_stats: stats_pipeline_layout,
+            pipeline_layout_hash: hash_pipeline_layout,
+            pipeline_layout_bonds: bond_pipeline_layout,
+            sim_format,
+            buf_stats,
+            stats_readback,
+
+            profiler,
+            profile_in_title: sim_config.profile_title,
 
             window_handle: window,
-            window_size: size,
-
-            uniforms,
-            uniform_buf_agent_compute: uniform_agent_compute,
-            uniform_buf_env_compute: uniform_env_compute,
+            title: String::new(),
+            window_size,
+            world_size: size,
+
+            _uniforms: uniforms,
+            _uniform_buf_agent_compute: uniform_agent_compute,
+            _uniform_buf_env_compute: uniform_env_compute,
+            _uniform_buf_agent_render: uniform_agent_render,
+            _uniform_buf_env_render: uniform_env_render,
             uniform_bindgroup_agent_compute: uniform_agent_compute_bindgroup,
             uniform_bindgroup_env_compute: uniform_env_compute_bindgroup,
+            _uniform_buf_hash_compute: uniform_hash_compute,
+            uniform_bindgroup_hash_compute: uniform_hash_compute_bindgroup,
+            uniform_buf_bond_compute: uniform_bond_compute,
+            uniform_bindgroup_bond_compute: uniform_bond_compute_bindgroup,
 
             frame_num: 0,
-        })
-    }
 
-    pub fn window(&self) -> &Window {
-        self.window_handle
+            device_lost,
+            checkpoint_interval: sim_config.checkpoint_interval,
+            checkpoint: None,
+        })
     }
 
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
@@ -526,11 +1009,426 @@ impl<'a> State<'a> {
             .configure(&self.gpu_device, &self.gpu_config);
     }
 
-    pub fn input_is_handled(&mut self, _event: &WindowEvent) -> bool {
-        false
+    /// Number of simulation steps run so far
+    pub fn frame(&self) -> u64 {
+        self.frame_num
+    }
+
+    /// Copies the most recently written env field back to the CPU. Blocks until the GPU is done,
+    /// so only use this for occasional snapshots.
+    /// `None` if the mapping failed, e.g. because the device was lost.
+    pub fn read_env(&self) -> Option<Vec<EnvCell>> {
+        self.read_buffer(&self.buf_env[StepBuffers::latest(self.frame_num)])
+    }
+
+    fn read_agents(&self) -> Option<Vec<Agent>> {
+        self.read_buffer(&self.buf_agents[StepBuffers::latest(self.frame_num)])
+    }
+
+    fn read_buffer<T: bytemuck::Pod>(&self, src: &wgpu::Buffer) -> Option<Vec<T>> {
+        let staging = self.gpu_device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size: src.size(),
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .gpu_device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Readback Encoder"),
+            });
+        encoder.copy_buffer_to_buffer(src, 0, &staging, 0, src.size());
+        self.gpu_queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = staging.slice(..);
+        let mapped = Arc::new(AtomicBool::new(false));
+        let mapped_cb = mapped.clone();
+        slice.map_async(wgpu::MapMode::Read, move |res| {
+            mapped_cb.store(res.is_ok(), Ordering::Release);
+        });
+        self.gpu_device.poll(wgpu::Maintain::Wait);
+        if !mapped.load(Ordering::Acquire) || self.is_device_lost() {
+            return None;
+        }
+        let cells = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        staging.unmap();
+        Some(cells)
+    }
+
+    /// Whether the GPU device has been lost, after which nothing more can be submitted and the
+    /// `State` has to be recreated
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Acquire)
+    }
+
+    /// Tears down a `State` whose device was lost, keeping what its replacement resumes from
+    pub fn into_lost(self) -> LostState {
+        LostState {
+            checkpoint: self.checkpoint,
+            stats_recorder: self.stats_recorder,
+            particle_life: self.particle_life,
+        }
     }
 
-    pub fn update(&mut self) {}
+    /// Takes over from a `State` whose device was lost, continuing its stats output and
+    /// interaction matrix from its latest checkpoint
+    pub fn resume(&mut self, lost: LostState) {
+        self.stats_recorder = lost.stats_recorder;
+        if let Some(particle_life) = lost.particle_life {
+            self.gpu_queue.write_buffer(
+                &self.buf_interactions,
+                0,
+                bytemuck::bytes_of(&particle_life.matrix),
+            );
+            self.particle_life = Some(particle_life);
+        }
+        match lost.checkpoint {
+            Some(checkpoint)
+                if (checkpoint.width, checkpoint.height)
+                    != (self.world_size.width, self.world_size.height) =>
+            {
+                println!(
+                    "Checkpoint is of a {}x{} world, not {}x{}; restarting the simulation",
+                    checkpoint.width,
+                    checkpoint.height,
+                    self.world_size.width,
+                    self.world_size.height
+                );
+            }
+            Some(checkpoint) => {
+                self.restore(&checkpoint);
+                // Kept, so losing the device again before the next checkpoint resumes from here
+                self.checkpoint = Some(checkpoint);
+            }
+            None => println!("No checkpoint to restore, restarting the simulation"),
+        }
+    }
+
+    /// Continues the simulation from a checkpoint taken by an earlier `State` of the same world
+    fn restore(&mut self, checkpoint: &Checkpoint) {
+        self.frame_num = checkpoint.frame;
+        let latest = StepBuffers::latest(self.frame_num);
+        self.gpu_queue.write_buffer(
+            &self.buf_agents[latest],
+            0,
+            bytemuck::cast_slice(&checkpoint.agents),
+        );
+        self.gpu_queue.write_buffer(
+            &self.buf_lifecycle,
+            0,
+            bytemuck::cast_slice(&Agent::lifecycle_buf_contents(&checkpoint.agents)),
+        );
+        self.gpu_queue.write_buffer(
+            &self.buf_env[latest],
+            0,
+            bytemuck::cast_slice(&checkpoint.env),
+        );
+        println!("Restored the simulation from step {}", checkpoint.frame);
+    }
+
+    /// Takes the interaction matrix editing keys when running particle life, uploading and
+    /// printing the matrix after each
+    pub fn input_is_handled(&mut self, event: &WindowEvent) -> bool {
+        let (Some(particle_life), WindowEvent::KeyboardInput { event, .. }) =
+            (&mut self.particle_life, event)
+        else {
+            return false;
+        };
+        if event.state != ElementState::Pressed || !particle_life.handle_key(&event.logical_key) {
+            return false;
+        }
+        self.gpu_queue.write_buffer(
+            &self.buf_interactions,
+            0,
+            bytemuck::bytes_of(&particle_life.matrix),
+        );
+        print!("{}", particle_life);
+        true
+    }
+
+    pub fn update(&mut self) {
+        self.reload_shaders();
+
+        if self.checkpoint_interval > 0 && self.frame_num.is_multiple_of(self.checkpoint_interval) {
+            // A failed readback keeps the previous checkpoint
+            if let (Some(agents), Some(env)) = (self.read_agents(), self.read_env()) {
+                self.checkpoint = Some(Checkpoint {
+                    frame: self.frame_num,
+                    width: self.world_size.width,
+                    height: self.world_size.height,
+                    agents,
+                    env,
+                });
+            }
+        }
+
+        if let Some(recorder) = &mut self.stats_recorder {
+            let cell_count = (self.world_size.width * self.world_size.height) as usize;
+            for (frame, bytes) in self.stats_readback.poll(&self.gpu_device) {
+                let raw: GpuStats = *bytemuck::from_bytes(&bytes);
+                let stats = FrameStats::from_gpu(frame, &raw, cell_count);
+                if let Err(e) = recorder.record(&stats) {
+                    eprintln!("ERROR: Could not write stats: {}", e);
+                }
+            }
+        }
+
+        if let Some(profiler) = &mut self.profiler {
+            profiler.poll(&self.gpu_device);
+            if self.frame_num > 0 && self.frame_num.is_multiple_of(PROFILE_PRINT_INTERVAL) {
+                println!("Profile: {}", profiler.summary());
+            }
+        }
+
+        let mut title_parts = vec!["Slime".to_string()];
+        if let Some(stats) = self.stats_recorder.as_ref().and_then(|r| r.title()) {
+            title_parts.push(stats.to_string());
+        }
+        if let Some(profiler) = self.profiler.as_ref().filter(|_| self.profile_in_title) {
+            title_parts.push(profiler.summary());
+        }
+        if title_parts.len() > 1 {
+            let title = title_parts.join(" | ");
+            if title != self.title {
+                self.window_handle.set_title(&title);
+                self.title = title;
+            }
+        }
+    }
+
+    /// Rebuilds the pipelines of edited shaders in place, leaving the simulation state alone.
+    /// A shader that fails to compile is reported and its previous pipelines keep running.
+    fn reload_shaders(&mut self) {
+        let Some(watcher) = &self.shader_watcher else {
+            return;
+        };
+        for shader in watcher.changed() {
+            let desc = match watcher.read(shader) {
+                Ok(desc) => desc,
+                Err(e) => {
+                    eprintln!("ERROR: Could not read {}: {}", shader.file_name(), e);
+                    continue;
+                }
+            };
+            if let wgpu::ShaderSource::Wgsl(source) = &desc.source {
+                let errors = layout::check_shader(shader.file_name(), source);
+                if !errors.is_empty() {
+                    eprintln!(
+                        "ERROR: Could not reload {}, keeping the previous version:\n{}",
+                        shader.file_name(),
+                        errors.join("\n")
+                    );
+                    continue;
+                }
+            }
+
+            let device = &self.gpu_device;
+            device.push_error_scope(wgpu::ErrorFilter::Validation);
+            let reloaded = match shader {
+                ShaderFile::AgentCompute => {
+                    let module =
+                        device.create_shader_module(hdr::shader_with_format(desc, self.sim_format));
+                    ReloadedPipelines::Agent(create_agent_pipelines(
+                        device,
+                        &self.pipeline_layout_agents,
+                        &module,
+                    ))
+                }
+                ShaderFile::EnvCompute => {
+                    let module =
+                        device.create_shader_module(hdr::shader_with_format(desc, self.sim_format));
+                    ReloadedPipelines::Env(create_env_pipelines(
+                        device,
+                        &self.pipeline_layout_env,
+                        &module,
+                    ))
+                }
+                ShaderFile::StatsCompute => {
+                    let module = device.create_shader_module(desc);
+                    ReloadedPipelines::Stats(create_stats_pipelines(
+                        device,
+                        &self.pipeline_layout_stats,
+                        &module,
+                    ))
+                }
+                ShaderFile::HashCompute => {
+                    let module = device.create_shader_module(desc);
+                    ReloadedPipelines::Hash(create_hash_pipelines(
+                        device,
+                        &self.pipeline_layout_hash,
+                        &module,
+                    ))
+                }
+                ShaderFile::BondCompute => {
+                    let module =
+                        device.create_shader_module(hdr::shader_with_format(desc, self.sim_format));
+                    ReloadedPipelines::Bonds(create_bond_pipelines(
+                        device,
+                        &self.pipeline_layout_bonds,
+                        &module,
+                    ))
+                }
+                ShaderFile::PlaneEnv | ShaderFile::PlaneAgent => {
+                    let module = device.create_shader_module(desc);
+                    let pipeline = create_plane_pipeline(
+                        device,
+                        &self.pipeline_layout_plane,
+                        &module,
+                        self.gpu_config.format,
+                        shader.file_name(),
+                    );
+                    if shader == ShaderFile::PlaneEnv {
+                        ReloadedPipelines::PlaneEnv(pipeline)
+                    } else {
+                        ReloadedPipelines::PlaneAgent(pipeline)
+                    }
+                }
+            };
+            if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+                eprintln!(
+                    "ERROR: Could not reload {}, keeping the previous version:\n{}",
+                    shader.file_name(),
+                    error
+                );
+                continue;
+            }
+
+            match reloaded {
+                ReloadedPipelines::Agent([compute, spawn, hunt, particle_life, settle]) => {
+                    self.pipeline_compute_agents = compute;
+                    self.pipeline_spawn_agents = spawn;
+                    self.pipeline_hunt_agents = hunt;
+                    self.pipeline_particle_life = particle_life;
+                    self.pipeline_settle_agents = settle;
+                }
+                ReloadedPipelines::Env([naive, tiled, blur_rows]) => {
+                    self.pipeline_compute_env_naive = naive;
+                    self.pipeline_compute_env_tiled = tiled;
+                    self.pipeline_blur_rows = blur_rows;
+                }
+                ReloadedPipelines::Stats([env, agents]) => {
+                    self.pipeline_stats_env = env;
+                    self.pipeline_stats_agents = agents;
+                }
+                ReloadedPipelines::Hash(pipelines) => self.pipelines_hash = pipelines,
+                ReloadedPipelines::Bonds(pipelines) => self.pipelines_bonds = pipelines,
+                ReloadedPipelines::PlaneEnv(pipeline) => self.pipeline_plane_env = pipeline,
+                ReloadedPipelines::PlaneAgent(pipeline) => self.pipeline_plane_agents = pipeline,
+            }
+            println!("Reloaded {}", shader.file_name());
+        }
+    }
+
+    /// Sorts the agents in buffer `read` into the spatial hash. The counts must have been
+    /// cleared first.
+    fn encode_hash_pass<'p>(
+        &'p self,
+        compute_pass: &mut wgpu::ComputePass<'p>,
+        buffers: StepBuffers,
+    ) {
+        let agent_groups = self.agent_capacity.div_ceil(64);
+        let block_groups = self.hash_grid.block_count();
+        let [count, scan_blocks, scan_block_sums, add_block_offsets, scatter] =
+            &self.pipelines_hash;
+
+        let bindgroup = &self.bindgroup_hash[PairBinding::ReadToWrite.index(buffers)];
+        compute_pass.set_bind_group(0, bindgroup, &[]);
+        compute_pass.set_bind_group(1, &self.uniform_bindgroup_hash_compute, &[]);
+        for (pipeline, groups) in [
+            (count, agent_groups),
+            (scan_blocks, block_groups),
+            (scan_block_sums, 1),
+            (add_block_offsets, block_groups),
+            (scatter, agent_groups),
+        ] {
+            compute_pass.set_pipeline(pipeline);
+            compute_pass.dispatch_workgroups(groups, 1, 1);
+        }
+    }
+
+    /// Pulls the organisms in the agent buffer written by the step back into shape, then draws
+    /// their bonds
+    fn encode_bond_pass<'p>(
+        &'p self,
+        compute_pass: &mut wgpu::ComputePass<'p>,
+        buffers: StepBuffers,
+    ) {
+        let groups = self.organism_cells.div_ceil(64);
+        let [pulse, solve, apply, draw] = &self.pipelines_bonds;
+
+        let bindgroup = &self.bindgroup_bonds[PairBinding::Written.index(buffers)];
+        compute_pass.set_bind_group(0, bindgroup, &[]);
+        compute_pass.set_bind_group(1, &self.uniform_bindgroup_bond_compute, &[]);
+        let iterations = (0..self.bond_iterations).flat_map(|_| [solve, apply]);
+        for pipeline in [pulse, apply].into_iter().chain(iterations).chain([draw]) {
+            compute_pass.set_pipeline(pipeline);
+            compute_pass.dispatch_workgroups(groups, 1, 1);
+        }
+    }
+
+    /// Diffuses the env buffer the step reads into the one it writes
+    fn encode_env_pass<'p>(
+        &'p self,
+        compute_pass: &mut wgpu::ComputePass<'p>,
+        pipeline: &'p wgpu::ComputePipeline,
+        buffers: StepBuffers,
+    ) {
+        let xgroups = self.world_size.width / 8;
+        let ygroups = self.world_size.height / 8;
+
+        let bindgroup = &self.bindgroup_compute_env[PairBinding::ReadToWrite.index(buffers)];
+        compute_pass.set_bind_group(0, bindgroup, &[]);
+        compute_pass.set_bind_group(1, &self.uniform_bindgroup_env_compute, &[]);
+        if self.diffusion == DiffusionKernel::Gaussian {
+            compute_pass.set_pipeline(&self.pipeline_blur_rows);
+            compute_pass.dispatch_workgroups(xgroups, ygroups, 1);
+        }
+        compute_pass.set_pipeline(pipeline);
+        compute_pass.dispatch_workgroups(xgroups, ygroups, 1);
+    }
+
+    /// Times `iterations` env passes with each of the naive and tiled kernels and prints the
+    /// mean time per pass. The passes ping-pong the env buffers, so this disturbs the field.
+    pub fn benchmark_diffusion(&self, iterations: u32) {
+        const WARMUP_ITERATIONS: u32 = 10;
+        println!(
+            "Benchmarking {:?} diffusion on a {}x{} world, {} passes each",
+            self.diffusion, self.world_size.width, self.world_size.height, iterations
+        );
+        for (name, pipeline) in [
+            ("naive", &self.pipeline_compute_env_naive),
+            ("tiled", &self.pipeline_compute_env_tiled),
+        ] {
+            let run = |count: u32| {
+                let mut encoder =
+                    self.gpu_device
+                        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                            label: Some("Diffusion Benchmark Encoder"),
+                        });
+                {
+                    let mut compute_pass =
+                        encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                            label: Some("Diffusion Benchmark Pass"),
+                            timestamp_writes: None,
+                        });
+                    for i in 0..count {
+                        self.encode_env_pass(
+                            &mut compute_pass,
+                            pipeline,
+                            StepBuffers::for_step(i as u64),
+                        );
+                    }
+                }
+                self.gpu_queue.submit(std::iter::once(encoder.finish()));
+                self.gpu_device.poll(wgpu::Maintain::Wait);
+            };
+            run(WARMUP_ITERATIONS);
+            let start = Instant::now();
+            run(iterations);
+            let ms = start.elapsed().as_secs_f64() * 1000.0 / iterations as f64;
+            println!("{}: {:.3}ms per pass", name, ms);
+        }
+    }
 
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
         let output = self.gpu_surface.get_current_texture()?;
@@ -543,6 +1441,138 @@ impl<'a> State<'a> {
                 label: Some("Render Encoder"),
             });
 
+        let buffers = StepBuffers::for_step(self.frame_num);
+
+        // Sense, move, deposit
+        {
+            let clear_agents = |encoder: &mut wgpu::CommandEncoder| {
+                encoder.clear_texture(
+                    &self._texture_agents,
+                    &wgpu::ImageSubresourceRange {
+                        aspect: wgpu::TextureAspect::All,
+                        base_mip_level: 0,
+                        mip_level_count: None,
+                        base_array_layer: 0,
+                        array_layer_count: None,
+                    },
+                )
+            };
+            match &self.profiler {
+                Some(profiler) => {
+                    profiler.time_commands(&mut encoder, ProfiledPass::TextureClear, clear_agents)
+                }
+                None => clear_agents(&mut encoder),
+            }
+        }
+
+        if self.lifecycle {
+            encoder.clear_buffer(&self.buf_lifecycle, BIRTH_COUNT_OFFSET, Some(4));
+        }
+        if self.predators {
+            encoder.clear_buffer(&self.buf_occupancy, 0, None);
+        }
+        if self.spatial_hash {
+            encoder.clear_buffer(&self.buf_hash_cells, 0, Some(self.hash_grid.counts_size()));
+        }
+        if self.organism_cells > 0 {
+            self.gpu_queue.write_buffer(
+                &self.uniform_buf_bond_compute,
+                BondComputeParams::step_offset(),
+                bytemuck::bytes_of(&(self.frame_num as u32)),
+            );
+        }
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Agent Compute Pass"),
+                timestamp_writes: self
+                    .profiler
+                    .as_ref()
+                    .and_then(|p| p.compute_timestamp_writes(ProfiledPass::AgentCompute)),
+            });
+
+            if self.spatial_hash {
+                self.encode_hash_pass(&mut compute_pass, buffers);
+            }
+
+            if self.particle_life.is_some() {
+                compute_pass.set_pipeline(&self.pipeline_particle_life);
+            } else {
+                compute_pass.set_pipeline(&self.pipeline_compute_agents);
+            }
+            let agent_bindgroup =
+                &self.bindgroup_compute_agents[PairBinding::ReadToWrite.index(buffers)];
+            compute_pass.set_bind_group(0, agent_bindgroup, &[]);
+            compute_pass.set_bind_group(1, &self.uniform_bindgroup_agent_compute, &[]);
+            compute_pass.dispatch_workgroups(self.agent_capacity.div_ceil(64), 1, 1);
+
+            // At most one birth per agent, so one invocation per slot covers them all
+            if self.lifecycle {
+                compute_pass.set_pipeline(&self.pipeline_spawn_agents);
+                compute_pass.dispatch_workgroups(self.agent_capacity.div_ceil(64), 1, 1);
+            }
+            // After everything else has moved the organism cells, and before the hunt, so
+            // predators find them where they are drawn
+            if self.organism_cells > 0 {
+                self.encode_bond_pass(&mut compute_pass, buffers);
+                compute_pass.set_pipeline(&self.pipeline_settle_agents);
+                compute_pass.set_bind_group(0, agent_bindgroup, &[]);
+                compute_pass.set_bind_group(1, &self.uniform_bindgroup_agent_compute, &[]);
+                compute_pass.dispatch_workgroups(self.organism_cells.div_ceil(64), 1, 1);
+            }
+            // After spawning, so prey eaten here have already placed their siblings
+            if self.predators {
+                compute_pass.set_pipeline(&self.pipeline_hunt_agents);
+                compute_pass.dispatch_workgroups(self.agent_capacity.div_ceil(64), 1, 1);
+            }
+        }
+
+        // Diffuse, decay
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Env Compute Pass"),
+                timestamp_writes: self
+                    .profiler
+                    .as_ref()
+                    .and_then(|p| p.compute_timestamp_writes(ProfiledPass::EnvCompute)),
+            });
+
+            let pipeline = if self.tiled_diffusion {
+                &self.pipeline_compute_env_tiled
+            } else {
+                &self.pipeline_compute_env_naive
+            };
+            self.encode_env_pass(&mut compute_pass, pipeline, buffers);
+        }
+        // The deposits have been merged into the field, start the next step from nothing
+        encoder.clear_buffer(&self.buf_deposits, 0, None);
+
+        // Stats
+        if self.stats_recorder.is_some() {
+            encoder.clear_buffer(&self.buf_stats, 0, None);
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Stats Compute Pass"),
+                timestamp_writes: None,
+            });
+            let bindgroup = &self.bindgroup_stats[PairBinding::Written.index(buffers)];
+            compute_pass.set_bind_group(0, bindgroup, &[]);
+
+            compute_pass.set_pipeline(&self.pipeline_stats_env);
+            compute_pass.dispatch_workgroups(
+                self.world_size.width.div_ceil(8),
+                self.world_size.height.div_ceil(8),
+                1,
+            );
+
+            compute_pass.set_pipeline(&self.pipeline_stats_agents);
+            compute_pass.dispatch_workgroups(self.agent_capacity.div_ceil(64), 1, 1);
+            drop(compute_pass);
+
+            self.stats_readback
+                .copy_from(&mut encoder, &self.buf_stats, self.frame_num);
+        }
+
+        // Render this step's textures
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Plane Render Pass"),
@@ -562,7 +1592,10 @@ impl<'a> State<'a> {
                 })],
                 depth_stencil_attachment: None,
                 occlusion_query_set: None,
-                timestamp_writes: None,
+                timestamp_writes: self
+                    .profiler
+                    .as_ref()
+                    .and_then(|p| p.render_timestamp_writes(ProfiledPass::PlaneRender)),
             });
 
             render_pass.set_pipeline(&self.pipeline_plane_env);
@@ -576,62 +1609,183 @@ impl<'a> State<'a> {
             render_pass.draw(0..(PLANE_VERTICES.len() as u32), 0..1);
         }
 
-        {
-            encoder.clear_texture(
-                &self._texture_agents,
-                &wgpu::ImageSubresourceRange {
-                    aspect: wgpu::TextureAspect::All,
-                    base_mip_level: 0,
-                    mip_level_count: None,
-                    base_array_layer: 0,
-                    array_layer_count: None,
-                },
-            );
+        if let Some(profiler) = &mut self.profiler {
+            profiler.resolve(&mut encoder, self.frame_num);
         }
 
-        {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Agent Compute Pass"),
-                timestamp_writes: None,
-            });
+        self.gpu_queue.submit(std::iter::once(encoder.finish()));
+        self.stats_readback.map_copied();
+        if let Some(profiler) = &mut self.profiler {
+            profiler.end_frame();
+        }
+        output.present();
 
-            let xdim = NUM_AGENTS as u32;
-            let xgroups = xdim / 8;
+        self.frame_num += 1;
 
-            compute_pass.set_pipeline(&self.pipeline_compute_agents);
-            compute_pass.set_bind_group(
-                0,
-                &self.bindgroup_compute_agents[(self.frame_num % 2) as usize],
-                &[],
-            );
-            compute_pass.set_bind_group(1, &self.uniform_bindgroup_agent_compute, &[]);
-            compute_pass.dispatch_workgroups(xgroups, 1, 1);
-        }
+        Ok(())
+    }
+}
 
-        {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Env Compute Pass"),
-                timestamp_writes: None,
-            });
+/// Pipelines rebuilt from an edited shader, before they replace the running ones
+enum ReloadedPipelines {
+    Agent([wgpu::ComputePipeline; 5]),
+    Env([wgpu::ComputePipeline; 3]),
+    Stats([wgpu::ComputePipeline; 2]),
+    Hash([wgpu::ComputePipeline; 5]),
+    Bonds([wgpu::ComputePipeline; 4]),
+    PlaneEnv(wgpu::RenderPipeline),
+    PlaneAgent(wgpu::RenderPipeline),
+}
 
-            let xgroups = self.window_size.width / 8;
-            let ygroups = self.window_size.height / 8;
+/// Pipeline drawing a textured plane to the surface with `vs_main` and `fs_main` of `module`
+fn create_plane_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    module: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    label: &str,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module,
+            entry_point: "vs_main",
+            buffers: &[Vertex::buf_desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        depth_stencil: None,
+        multiview: None,
+    })
+}
 
-            compute_pass.set_pipeline(&self.pipeline_compute_env);
-            compute_pass.set_bind_group(
-                0,
-                &self.bindgroup_compute_env[(self.frame_num % 2) as usize],
-                &[],
-            );
-            compute_pass.set_bind_group(1, &self.uniform_bindgroup_env_compute, &[]);
-            compute_pass.dispatch_workgroups(xgroups, ygroups, 1);
-        }
+/// The agent step, spawn, hunt and particle life pipelines
+fn create_agent_pipelines(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    module: &wgpu::ShaderModule,
+) -> [wgpu::ComputePipeline; 5] {
+    [
+        ("Agent Compute Pipeline", "compute_main"),
+        ("Agent Spawn Pipeline", "spawn_main"),
+        ("Agent Hunt Pipeline", "hunt_main"),
+        ("Particle Life Pipeline", "particle_life_main"),
+        ("Agent Settle Pipeline", "settle_main"),
+    ]
+    .map(|(label, entry_point)| {
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            module,
+            entry_point,
+        })
+    })
+}
 
-        self.gpu_queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+/// The naive env, tiled env and Gaussian row pipelines
+fn create_env_pipelines(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    module: &wgpu::ShaderModule,
+) -> [wgpu::ComputePipeline; 3] {
+    [
+        ("Env Compute Pipeline", "compute_main"),
+        ("Env Tiled Compute Pipeline", "compute_tiled_main"),
+        ("Blur Rows Compute Pipeline", "blur_rows_main"),
+    ]
+    .map(|(label, entry_point)| {
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            module,
+            entry_point,
+        })
+    })
+}
 
-        self.frame_num += 1;
+/// The env and agent stats pipelines
+fn create_stats_pipelines(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    module: &wgpu::ShaderModule,
+) -> [wgpu::ComputePipeline; 2] {
+    [
+        ("Env Stats Compute Pipeline", "env_stats_main"),
+        ("Agent Stats Compute Pipeline", "agent_stats_main"),
+    ]
+    .map(|(label, entry_point)| {
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            module,
+            entry_point,
+        })
+    })
+}
 
-        Ok(())
-    }
+/// The spatial hash pipelines, in dispatch order
+fn create_hash_pipelines(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    module: &wgpu::ShaderModule,
+) -> [wgpu::ComputePipeline; 5] {
+    [
+        ("Hash Count Pipeline", "count_main"),
+        ("Hash Scan Blocks Pipeline", "scan_blocks_main"),
+        ("Hash Scan Block Sums Pipeline", "scan_block_sums_main"),
+        ("Hash Block Offsets Pipeline", "add_block_offsets_main"),
+        ("Hash Scatter Pipeline", "scatter_main"),
+    ]
+    .map(|(label, entry_point)| {
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            module,
+            entry_point,
+        })
+    })
+}
+
+/// The organism pulse, solve, apply and draw pipelines
+fn create_bond_pipelines(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    module: &wgpu::ShaderModule,
+) -> [wgpu::ComputePipeline; 4] {
+    [
+        ("Bond Pulse Pipeline", "pulse_main"),
+        ("Bond Solve Pipeline", "solve_main"),
+        ("Bond Apply Pipeline", "apply_main"),
+        ("Bond Draw Pipeline", "draw_main"),
+    ]
+    .map(|(label, entry_point)| {
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            module,
+            entry_point,
+        })
+    })
 }
@@ -0,0 +1,132 @@
+//! Format of the env and agent storage textures. Float textures keep trail levels above 1.0
+//! and avoid banding at low levels, leaving the plane shaders to tone map them for display.
+
+use crate::config::Config;
+
+/// Format the compute shaders write when the adapter can bind it as a storage texture
+const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// Format used when float storage textures are unavailable or disabled
+const LDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+/// Declaration of the storage texture type in the compute shaders, as written for `LDR_FORMAT`
+const LDR_TEXTURE_ALIAS: &str = "alias SimTexture = texture_storage_2d<rgba8unorm, write>;";
+/// The same declaration for `HDR_FORMAT`
+const HDR_TEXTURE_ALIAS: &str = "alias SimTexture = texture_storage_2d<rgba16float, write>;";
+
+/// Picks the storage texture format for the env and agent layers
+pub fn sim_texture_format(adapter: &wgpu::Adapter, config: &Config) -> wgpu::TextureFormat {
+    if config.ldr_textures {
+        return LDR_FORMAT;
+    }
+    if supports_hdr(adapter) {
+        HDR_FORMAT
+    } else {
+        println!(
+            "Float storage textures unsupported, falling back to {:?}",
+            LDR_FORMAT
+        );
+        LDR_FORMAT
+    }
+}
+
+/// Whether the adapter can bind float textures as storage textures
+pub fn supports_hdr(adapter: &wgpu::Adapter) -> bool {
+    adapter
+        .get_texture_format_features(HDR_FORMAT)
+        .allowed_usages
+        .contains(wgpu::TextureUsages::STORAGE_BINDING)
+}
+
+pub fn is_hdr(format: wgpu::TextureFormat) -> bool {
+    format == HDR_FORMAT
+}
+
+/// Declares the `SimTexture` storage texture type of a WGSL shader in `format`
+pub fn shader_with_format(
+    desc: wgpu::ShaderModuleDescriptor<'static>,
+    format: wgpu::TextureFormat,
+) -> wgpu::ShaderModuleDescriptor<'static> {
+    let source = match desc.source {
+        wgpu::ShaderSource::Wgsl(source) if format == HDR_FORMAT => wgpu::ShaderSource::Wgsl(
+            source
+                .replacen(LDR_TEXTURE_ALIAS, HDR_TEXTURE_ALIAS, 1)
+                .into(),
+        ),
+        source => source,
+    };
+    wgpu::ShaderModuleDescriptor {
+        label: desc.label,
+        source,
+    }
+}
+
+/// The layout's entries with every storage texture switched to `format`
+pub fn layout_entries_with_format(
+    desc: &wgpu::BindGroupLayoutDescriptor,
+    format: wgpu::TextureFormat,
+) -> Vec<wgpu::BindGroupLayoutEntry> {
+    desc.entries
+        .iter()
+        .map(|entry| match entry.ty {
+            wgpu::BindingType::StorageTexture {
+                access,
+                view_dimension,
+                ..
+            } => wgpu::BindGroupLayoutEntry {
+                ty: wgpu::BindingType::StorageTexture {
+                    access,
+                    format,
+                    view_dimension,
+                },
+                ..*entry
+            },
+            _ => *entry,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHADERS: [&str; 3] = [
+        include_str!("shader_compute_agent.wgsl"),
+        include_str!("shader_compute_bonds.wgsl"),
+        include_str!("shader_compute_env.wgsl"),
+    ];
+
+    /// Formats of the storage textures a shader declares
+    fn storage_formats(desc: wgpu::ShaderModuleDescriptor) -> Vec<naga::StorageFormat> {
+        let wgpu::ShaderSource::Wgsl(source) = desc.source else {
+            panic!("not a WGSL shader");
+        };
+        let module = naga::front::wgsl::parse_str(&source).unwrap();
+        module
+            .global_variables
+            .iter()
+            .filter_map(|(_, var)| match module.types[var.ty].inner {
+                naga::TypeInner::Image {
+                    class: naga::ImageClass::Storage { format, .. },
+                    ..
+                } => Some(format),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn desc(source: &'static str) -> wgpu::ShaderModuleDescriptor<'static> {
+        wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        }
+    }
+
+    #[test]
+    fn storage_textures_take_the_chosen_format() {
+        for source in SHADERS {
+            let ldr = storage_formats(shader_with_format(desc(source), LDR_FORMAT));
+            assert_eq!(ldr, [naga::StorageFormat::Rgba8Unorm]);
+            let hdr = storage_formats(shader_with_format(desc(source), HDR_FORMAT));
+            assert_eq!(hdr, [naga::StorageFormat::Rgba16Float]);
+        }
+    }
+}
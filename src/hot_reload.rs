@@ -0,0 +1,106 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+
+use notify::{RecursiveMode, Watcher};
+
+/// A WGSL file that can be reloaded while the simulation runs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderFile {
+    AgentCompute,
+    EnvCompute,
+    StatsCompute,
+    HashCompute,
+    BondCompute,
+    PlaneEnv,
+    PlaneAgent,
+}
+
+const SHADER_FILES: [ShaderFile; 7] = [
+    ShaderFile::AgentCompute,
+    ShaderFile::EnvCompute,
+    ShaderFile::StatsCompute,
+    ShaderFile::HashCompute,
+    ShaderFile::BondCompute,
+    ShaderFile::PlaneEnv,
+    ShaderFile::PlaneAgent,
+];
+
+impl ShaderFile {
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Self::AgentCompute => "shader_compute_agent.wgsl",
+            Self::EnvCompute => "shader_compute_env.wgsl",
+            Self::StatsCompute => "shader_compute_stats.wgsl",
+            Self::HashCompute => "shader_compute_hash.wgsl",
+            Self::BondCompute => "shader_compute_bonds.wgsl",
+            Self::PlaneEnv => "shader_plane_env.wgsl",
+            Self::PlaneAgent => "shader_plane_agent.wgsl",
+        }
+    }
+
+    fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?;
+        SHADER_FILES.into_iter().find(|f| name == f.file_name())
+    }
+}
+
+/// Watches the source directory the shaders were embedded from and reports edited shaders
+pub struct ShaderWatcher {
+    dir: PathBuf,
+    events: Receiver<notify::Result<notify::Event>>,
+    // Dropping the watcher stops the events
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl ShaderWatcher {
+    pub fn new() -> notify::Result<Self> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let (sender, events) = channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // The receiver only goes away with the watcher
+            let _ = sender.send(event);
+        })?;
+        watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+        println!("Watching {} for shader changes", dir.display());
+        Ok(Self {
+            dir,
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    /// Shaders written to since the last call, each listed once
+    pub fn changed(&self) -> Vec<ShaderFile> {
+        let mut changed = Vec::new();
+        for event in self.events.try_iter() {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    eprintln!("ERROR: Shader watcher: {}", e);
+                    continue;
+                }
+            };
+            // Editors that save by renaming a temporary file show up as creations
+            if !(event.kind.is_modify() || event.kind.is_create()) {
+                continue;
+            }
+            for shader in event.paths.iter().filter_map(|p| ShaderFile::from_path(p)) {
+                if !changed.contains(&shader) {
+                    changed.push(shader);
+                }
+            }
+        }
+        changed
+    }
+
+    pub fn read(
+        &self,
+        shader: ShaderFile,
+    ) -> std::io::Result<wgpu::ShaderModuleDescriptor<'static>> {
+        let source = std::fs::read_to_string(self.dir.join(shader.file_name()))?;
+        Ok(wgpu::ShaderModuleDescriptor {
+            label: Some(shader.file_name()),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        })
+    }
+}
@@ -0,0 +1,490 @@
+//! Checks that the structs the shaders declare match the `#[repr(C)]` Rust structs uploaded
+//! into them. Structs shared with WGSL are declared through `shader_struct!`, which records
+//! each field's offset, size and WGSL type; `check_layouts` then compares them against the
+//! layouts naga computes for the shader source. Shader constants that mirror Rust constants
+//! are checked against them too.
+
+use crate::agents::Agent;
+use crate::environment::EnvCell;
+use crate::organisms::Bond;
+use crate::params::{
+    AgentComputeParams, AgentRenderParams, BondComputeParams, EnvComputeParams, EnvRenderParams,
+    HashComputeParams,
+};
+use crate::particle_life::InteractionMatrix;
+use crate::stats::{GpuStats, TRAIT_DISTANCE, TRAIT_RANGES, TRAIT_SPEED, TRAIT_SPREAD, TRAIT_TURN};
+
+/// A field of a Rust struct shared with WGSL
+pub struct FieldLayout {
+    pub name: &'static str,
+    pub offset: usize,
+    pub size: usize,
+    pub wgsl_type: String,
+}
+
+/// Rust types with a WGSL equivalent
+pub trait WgslType {
+    fn wgsl_type() -> String;
+}
+
+impl WgslType for f32 {
+    fn wgsl_type() -> String {
+        "f32".to_string()
+    }
+}
+
+impl WgslType for u32 {
+    fn wgsl_type() -> String {
+        "u32".to_string()
+    }
+}
+
+/// Arrays of two to four elements are vectors, e.g. the `[u32; 2]` world dimensions
+impl<T: WgslType, const N: usize> WgslType for [T; N] {
+    fn wgsl_type() -> String {
+        if (2..=4).contains(&N) {
+            format!("vec{}<{}>", N, T::wgsl_type())
+        } else {
+            format!("array<{}, {}>", T::wgsl_type(), N)
+        }
+    }
+}
+
+pub trait ShaderStruct: Sized {
+    fn fields() -> Vec<FieldLayout>;
+
+    /// WGSL declaration of the struct under `name`, leaving out padding fields
+    fn wgsl_decl(name: &str) -> String {
+        let fields: String = Self::fields()
+            .iter()
+            .filter(|f| !f.name.starts_with('_'))
+            .map(|f| format!("    {}: {},\n", f.name, f.wgsl_type))
+            .collect();
+        format!("struct {} {{\n{}}}", name, fields)
+    }
+}
+
+/// Declares a struct shared with WGSL, implementing `ShaderStruct` for it. Fields starting
+/// with `_` are padding that the WGSL declaration leaves out.
+macro_rules! shader_struct {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$field_attr:meta])* $field_vis:vis $field:ident: $ty:ty,)*
+        }
+    ) => {
+        $(#[$attr])*
+        $vis struct $name {
+            $($(#[$field_attr])* $field_vis $field: $ty,)*
+        }
+
+        impl $crate::layout::ShaderStruct for $name {
+            fn fields() -> Vec<$crate::layout::FieldLayout> {
+                vec![$($crate::layout::FieldLayout {
+                    name: stringify!($field),
+                    offset: std::mem::offset_of!($name, $field),
+                    size: std::mem::size_of::<$ty>(),
+                    wgsl_type: <$ty as $crate::layout::WgslType>::wgsl_type(),
+                },)*]
+            }
+        }
+    };
+}
+pub(crate) use shader_struct;
+
+/// How a WGSL struct's size must relate to the Rust one
+#[derive(Clone, Copy)]
+enum Span {
+    /// Array elements: the strides must be equal
+    Exact,
+    /// Uniforms: the Rust struct may carry trailing padding
+    AtMost,
+}
+
+struct SharedStruct {
+    shader: &'static str,
+    wgsl_name: &'static str,
+    span: Span,
+    rust_name: &'static str,
+    rust_size: usize,
+    fields: Vec<FieldLayout>,
+    decl: String,
+}
+
+fn shared<T: ShaderStruct>(
+    shader: &'static str,
+    wgsl_name: &'static str,
+    span: Span,
+) -> SharedStruct {
+    SharedStruct {
+        shader,
+        wgsl_name,
+        span,
+        rust_name: std::any::type_name::<T>(),
+        rust_size: std::mem::size_of::<T>(),
+        fields: T::fields(),
+        decl: T::wgsl_decl(wgsl_name),
+    }
+}
+
+/// Every struct the shaders share with Rust
+fn shared_structs() -> Vec<SharedStruct> {
+    let agent = "shader_compute_agent.wgsl";
+    let env = "shader_compute_env.wgsl";
+    let stats = "shader_compute_stats.wgsl";
+    let hash = "shader_compute_hash.wgsl";
+    let bonds = "shader_compute_bonds.wgsl";
+    let plane_env = "shader_plane_env.wgsl";
+    let plane_agent = "shader_plane_agent.wgsl";
+    vec![
+        shared::<Agent>(agent, "Agent", Span::Exact),
+        shared::<EnvCell>(agent, "EnvCell", Span::Exact),
+        shared::<AgentComputeParams>(agent, "Uniforms", Span::AtMost),
+        shared::<InteractionMatrix>(agent, "Interactions", Span::Exact),
+        shared::<EnvCell>(env, "EnvCell", Span::Exact),
+        shared::<EnvComputeParams>(env, "Uniforms", Span::AtMost),
+        shared::<Agent>(stats, "Agent", Span::Exact),
+        shared::<EnvCell>(stats, "EnvCell", Span::Exact),
+        shared::<GpuStats>(stats, "Stats", Span::Exact),
+        shared::<Agent>(hash, "Agent", Span::Exact),
+        shared::<HashComputeParams>(hash, "Uniforms", Span::AtMost),
+        shared::<Agent>(bonds, "Agent", Span::Exact),
+        shared::<Bond>(bonds, "Bond", Span::Exact),
+        shared::<BondComputeParams>(bonds, "Uniforms", Span::AtMost),
+        shared::<EnvRenderParams>(plane_env, "RenderParams", Span::AtMost),
+        shared::<AgentRenderParams>(plane_agent, "RenderParams", Span::AtMost),
+    ]
+}
+
+/// A shader constant with the value of a Rust constant, an array flattened to its elements
+struct SharedConstant {
+    shader: &'static str,
+    wgsl_name: &'static str,
+    rust_name: &'static str,
+    values: Vec<f32>,
+}
+
+/// Every constant the shaders share with Rust
+fn shared_constants() -> Vec<SharedConstant> {
+    let agent = "shader_compute_agent.wgsl";
+    let stats = "shader_compute_stats.wgsl";
+    let trait_max = |wgsl_name, rust_name, index: usize| SharedConstant {
+        shader: agent,
+        wgsl_name,
+        rust_name,
+        values: vec![TRAIT_RANGES[index].1],
+    };
+    vec![
+        trait_max(
+            "MAX_SENSOR_SPREAD",
+            "TRAIT_RANGES[TRAIT_SPREAD]",
+            TRAIT_SPREAD,
+        ),
+        trait_max(
+            "MAX_SENSOR_DISTANCE",
+            "TRAIT_RANGES[TRAIT_DISTANCE]",
+            TRAIT_DISTANCE,
+        ),
+        trait_max("MAX_SPEED", "TRAIT_RANGES[TRAIT_SPEED]", TRAIT_SPEED),
+        trait_max("MAX_TURN_STRENGTH", "TRAIT_RANGES[TRAIT_TURN]", TRAIT_TURN),
+        SharedConstant {
+            shader: stats,
+            wgsl_name: "TRAIT_MAX",
+            rust_name: "TRAIT_RANGES",
+            values: TRAIT_RANGES.iter().map(|(_, max)| *max).collect(),
+        },
+    ]
+}
+
+/// The shaders as embedded in the binary
+const EMBEDDED_SHADERS: [(&str, &str); 7] = [
+    (
+        "shader_compute_agent.wgsl",
+        include_str!("shader_compute_agent.wgsl"),
+    ),
+    (
+        "shader_compute_env.wgsl",
+        include_str!("shader_compute_env.wgsl"),
+    ),
+    (
+        "shader_compute_stats.wgsl",
+        include_str!("shader_compute_stats.wgsl"),
+    ),
+    (
+        "shader_compute_hash.wgsl",
+        include_str!("shader_compute_hash.wgsl"),
+    ),
+    (
+        "shader_compute_bonds.wgsl",
+        include_str!("shader_compute_bonds.wgsl"),
+    ),
+    (
+        "shader_plane_env.wgsl",
+        include_str!("shader_plane_env.wgsl"),
+    ),
+    (
+        "shader_plane_agent.wgsl",
+        include_str!("shader_plane_agent.wgsl"),
+    ),
+];
+
+/// Name of a naga type as `WgslType` spells it. Atomics are named after the value they hold,
+/// which is all the Rust side sees of them.
+fn wgsl_type_name(module: &naga::Module, ty: naga::Handle<naga::Type>) -> String {
+    match &module.types[ty].inner {
+        naga::TypeInner::Scalar(scalar) | naga::TypeInner::Atomic(scalar) => scalar_name(*scalar),
+        naga::TypeInner::Vector { size, scalar } => {
+            format!("vec{}<{}>", *size as u8, scalar_name(*scalar))
+        }
+        naga::TypeInner::Array {
+            base,
+            size: naga::ArraySize::Constant(count),
+            ..
+        } => format!("array<{}, {}>", wgsl_type_name(module, *base), count),
+        other => format!("{:?}", other),
+    }
+}
+
+fn scalar_name(scalar: naga::Scalar) -> String {
+    let prefix = match scalar.kind {
+        naga::ScalarKind::Float => "f",
+        naga::ScalarKind::Uint => "u",
+        naga::ScalarKind::Sint => "i",
+        kind => return format!("{:?}", kind).to_lowercase(),
+    };
+    format!("{}{}", prefix, scalar.width * 8)
+}
+
+/// Compares one shared struct against the shader, returning a description of each mismatch
+fn check_struct(shared: &SharedStruct, module: &naga::Module) -> Vec<String> {
+    let mut layouter = naga::proc::Layouter::default();
+    if let Err(e) = layouter.update(module.to_ctx()) {
+        return vec![format!("{}: could not lay out types: {}", shared.shader, e)];
+    }
+    let found = module.types.iter().find_map(|(_, ty)| match &ty.inner {
+        naga::TypeInner::Struct { members, span }
+            if ty.name.as_deref() == Some(shared.wgsl_name) =>
+        {
+            Some((members, *span))
+        }
+        _ => None,
+    });
+    let Some((members, span)) = found else {
+        return vec![format!(
+            "{}: struct {} (for {}) not found",
+            shared.shader, shared.wgsl_name, shared.rust_name
+        )];
+    };
+
+    let context = format!(
+        "{}: struct {} vs {}",
+        shared.shader, shared.wgsl_name, shared.rust_name
+    );
+    let mut errors = Vec::new();
+    let fields: Vec<&FieldLayout> = shared
+        .fields
+        .iter()
+        .filter(|f| !f.name.starts_with('_'))
+        .collect();
+    if members.len() != fields.len() {
+        errors.push(format!(
+            "{}: {} members in WGSL, {} fields in Rust",
+            context,
+            members.len(),
+            fields.len()
+        ));
+    }
+    for (member, field) in members.iter().zip(&fields) {
+        let name = member.name.as_deref().unwrap_or("?");
+        let size = layouter[member.ty].size as usize;
+        let ty = wgsl_type_name(module, member.ty);
+        if name != field.name
+            || member.offset as usize != field.offset
+            || size != field.size
+            || ty != field.wgsl_type
+        {
+            errors.push(format!(
+                "{}: WGSL member {}: {} at offset {} ({} bytes), Rust field {}: {} at offset {} ({} bytes)",
+                context,
+                name,
+                ty,
+                member.offset,
+                size,
+                field.name,
+                field.wgsl_type,
+                field.offset,
+                field.size
+            ));
+        }
+    }
+    let span = span as usize;
+    match shared.span {
+        Span::Exact if span != shared.rust_size => errors.push(format!(
+            "{}: {} bytes in WGSL, {} in Rust",
+            context, span, shared.rust_size
+        )),
+        Span::AtMost if span > shared.rust_size => errors.push(format!(
+            "{}: {} bytes in WGSL, only {} in Rust",
+            context, span, shared.rust_size
+        )),
+        _ => {}
+    }
+    if !errors.is_empty() {
+        errors.push(format!("expected declaration:\n{}", shared.decl));
+    }
+    errors
+}
+
+/// Values of an `f32` constant expression, following references to other constants
+fn constant_values(
+    module: &naga::Module,
+    expr: naga::Handle<naga::Expression>,
+) -> Option<Vec<f32>> {
+    match &module.const_expressions[expr] {
+        naga::Expression::Literal(naga::Literal::F32(value)) => Some(vec![*value]),
+        naga::Expression::Constant(constant) => {
+            constant_values(module, module.constants[*constant].init)
+        }
+        naga::Expression::Compose { components, .. } => components
+            .iter()
+            .map(|&component| constant_values(module, component))
+            .collect::<Option<Vec<_>>>()
+            .map(|values| values.concat()),
+        _ => None,
+    }
+}
+
+/// Compares one shared constant against the shader, returning a description of a mismatch
+fn check_constant(shared: &SharedConstant, module: &naga::Module) -> Option<String> {
+    let found = module
+        .constants
+        .iter()
+        .find(|(_, constant)| constant.name.as_deref() == Some(shared.wgsl_name));
+    let Some((_, constant)) = found else {
+        return Some(format!(
+            "{}: const {} (for {}) not found",
+            shared.shader, shared.wgsl_name, shared.rust_name
+        ));
+    };
+    match constant_values(module, constant.init) {
+        Some(values) if values == shared.values => None,
+        Some(values) => Some(format!(
+            "{}: const {} is {:?}, {} is {:?}",
+            shared.shader, shared.wgsl_name, values, shared.rust_name, shared.values
+        )),
+        None => Some(format!(
+            "{}: const {} (for {}) is not made of f32 literals",
+            shared.shader, shared.wgsl_name, shared.rust_name
+        )),
+    }
+}
+
+/// Checks the structs and constants a shader shares with Rust, given its file name and
+/// source, returning a description of each mismatch
+pub fn check_shader(file_name: &str, source: &str) -> Vec<String> {
+    let module = match naga::front::wgsl::parse_str(source) {
+        Ok(module) => module,
+        Err(e) => return vec![format!("{}: {}", file_name, e.emit_to_string(source))],
+    };
+    let constants = shared_constants()
+        .into_iter()
+        .filter(|shared| shared.shader == file_name)
+        .filter_map(|shared| check_constant(&shared, &module));
+    shared_structs()
+        .iter()
+        .filter(|shared| shared.shader == file_name)
+        .flat_map(|shared| check_struct(shared, &module))
+        .chain(constants)
+        .collect()
+}
+
+/// Checks every shared struct and constant in the embedded shaders
+pub fn check_layouts() -> Result<(), Vec<String>> {
+    let errors: Vec<String> = EMBEDDED_SHADERS
+        .iter()
+        .flat_map(|(file_name, source)| check_shader(file_name, source))
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Only reads an agent's position and alive flag, so it still compiles with the others edited
+    const HASH_SHADER: &str = "shader_compute_hash.wgsl";
+
+    fn hash_shader_with(from: &str, to: &str) -> String {
+        let source = include_str!("shader_compute_hash.wgsl");
+        assert!(source.contains(from), "hash shader has no {:?}", from);
+        source.replacen(from, to, 1)
+    }
+
+    fn mentions_agent(errors: &[String]) -> bool {
+        errors.iter().any(|e| e.contains("struct Agent vs"))
+    }
+
+    #[test]
+    fn embedded_shaders_match() {
+        assert_eq!(check_layouts(), Ok(()));
+    }
+
+    #[test]
+    fn missing_field_is_reported() {
+        let source = hash_shader_with("    energy: f32,\n", "");
+        let errors = check_shader(HASH_SHADER, &source);
+        assert!(mentions_agent(&errors), "{:?}", errors);
+    }
+
+    #[test]
+    fn changed_field_type_is_reported() {
+        let source = hash_shader_with("    age: u32,\n", "    age: f32,\n");
+        let errors = check_shader(HASH_SHADER, &source);
+        assert!(mentions_agent(&errors), "{:?}", errors);
+    }
+
+    #[test]
+    fn reordered_fields_are_reported() {
+        let source = hash_shader_with(
+            "    angle: f32,\n    turn_speed: f32,\n",
+            "    turn_speed: f32,\n    angle: f32,\n",
+        );
+        let errors = check_shader(HASH_SHADER, &source);
+        assert!(mentions_agent(&errors), "{:?}", errors);
+    }
+
+    #[test]
+    fn changed_trait_limit_is_reported() {
+        let file_name = "shader_compute_agent.wgsl";
+        let source = include_str!("shader_compute_agent.wgsl");
+        let from = "const MAX_SPEED: f32 = 4.0;";
+        assert!(source.contains(from));
+        let errors = check_shader(
+            file_name,
+            &source.replacen(from, "const MAX_SPEED: f32 = 5.0;", 1),
+        );
+        assert!(
+            errors.iter().any(|e| e.contains("MAX_SPEED")),
+            "{:?}",
+            errors
+        );
+
+        let file_name = "shader_compute_stats.wgsl";
+        let source = include_str!("shader_compute_stats.wgsl");
+        let from = "(3.14159265, 32.0, 4.0, 1.0)";
+        assert!(source.contains(from));
+        let errors = check_shader(
+            file_name,
+            &source.replacen(from, "(3.14159265, 16.0, 4.0, 1.0)", 1),
+        );
+        assert!(
+            errors.iter().any(|e| e.contains("TRAIT_MAX")),
+            "{:?}",
+            errors
+        );
+    }
+}
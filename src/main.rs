@@ -1,64 +1,236 @@
-#[macro_use]
-extern crate lazy_static;
+use clap::Parser;
 use winit::{dpi::PhysicalSize, event::*, event_loop::EventLoop, window::WindowBuilder};
+mod adapter;
 mod agents;
+mod analysis;
+mod checkpoint;
+mod config;
 mod environment;
+mod error;
+mod food;
 mod gpu;
+mod hdr;
+mod hot_reload;
+mod layout;
+mod network;
+mod obstacles;
+mod organisms;
 mod params;
+mod particle_life;
+mod profiler;
+mod readback;
 mod render_plane;
+mod spatial_hash;
+mod stats;
+mod step;
+
+/// Pause between attempts to recreate a lost GPU device
+const DEVICE_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+/// Initial window size, which is also the size of the world in cells
+const WINDOW_SIZE: PhysicalSize<u32> = PhysicalSize::new(1000, 1000);
 
 fn main() {
     env_logger::init();
+    let mut config = config::Config::parse();
+    if let Err(e) = config.validate() {
+        eprintln!("ERROR: {}", e);
+        std::process::exit(1);
+    }
+
+    if config.list_adapters {
+        adapter::list_adapters(&config, WINDOW_SIZE.width, WINDOW_SIZE.height);
+        return;
+    }
+    if config.check_layouts {
+        match layout::check_layouts() {
+            Ok(()) => println!("Shader struct layouts match"),
+            Err(errors) => {
+                for e in errors {
+                    eprintln!("ERROR: Shader layout mismatch: {}", e);
+                }
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
     let event_loop = EventLoop::new().unwrap();
     let window = WindowBuilder::new()
         .with_title("Slime")
-        .with_inner_size(PhysicalSize::new(1000, 1000))
+        .with_inner_size(WINDOW_SIZE)
         .build(&event_loop)
         .unwrap();
-    let mut state =
-        pollster::block_on(gpu::State::new(&window)).expect("GPU Initialization failed");
+
+    let world_size = window.inner_size();
+    let scenario =
+        network::NetworkScenario::from_config(&config, world_size.width, world_size.height)
+            .unwrap_or_else(|e| {
+                eprintln!("ERROR: Could not load network nodes: {}", e);
+                std::process::exit(1);
+            });
+    if let Some(scenario) = &scenario {
+        let sources = scenario.food_sources(food::DEFAULT_EMISSION_RATE);
+        config.food_sources.extend(sources);
+    }
+
+    let skeleton_worker = analysis::SkeletonRecorder::from_config(&config)
+        .unwrap_or_else(|e| {
+            eprintln!("ERROR: Could not create skeleton analysis output: {}", e);
+            std::process::exit(1);
+        })
+        .map(|recorder| recorder.spawn(world_size.width, world_size.height));
+
+    let stats_recorder = stats::StatsRecorder::from_config(&config).unwrap_or_else(|e| {
+        eprintln!("ERROR: Could not create stats output: {}", e);
+        std::process::exit(1);
+    });
+
+    let init = gpu::State::new(&window, world_size, &config, stats_recorder);
+    let state = pollster::block_on(init).unwrap_or_else(|e| {
+        eprintln!("ERROR: GPU initialization failed: {}", e);
+        std::process::exit(1);
+    });
+
+    if config.benchmark_diffusion > 0 {
+        state.benchmark_diffusion(config.benchmark_diffusion);
+        return;
+    }
 
     event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
 
+    // Whether the surface is timing out, so a run of timeouts is only reported once
+    let mut surface_timing_out = false;
+    let window = &window;
+    // None between losing the GPU device and recreating it, with what the lost state left
+    let mut state = Some(state);
+    let mut lost = None;
+
     event_loop
         .run(move |event, elwt| match event {
             Event::WindowEvent {
                 ref event,
                 window_id,
-            } if window_id == state.window().id() => {
-                if !state.input_is_handled(event) {
-                    match event {
-                        WindowEvent::CloseRequested => {
-                            println!("The close button was pressed; stopping");
-                            elwt.exit();
-                        }
-                        WindowEvent::Resized(physical_size) => {
+            } if window_id == window.id()
+                && !state.as_mut().is_some_and(|s| s.input_is_handled(event)) =>
+            {
+                match event {
+                    WindowEvent::CloseRequested => {
+                        println!("The close button was pressed; stopping");
+                        elwt.exit();
+                    }
+                    // A recreated state picks up the window's size when it is created
+                    WindowEvent::Resized(physical_size) => {
+                        if let Some(state) = &mut state {
                             println!("Resizing window");
                             state.resize(*physical_size);
                         }
-                        _ => {}
                     }
+                    _ => {}
                 }
             }
             Event::AboutToWait => {
+                let state = match &mut state {
+                    Some(current) if !current.is_device_lost() => current,
+                    _ => {
+                        // Drop the lost state first, as its surface still belongs to the window
+                        if let Some(current) = state.take() {
+                            lost = Some(current.into_lost());
+                        }
+                        match recover_device(window, world_size, &config, &mut lost) {
+                            Ok(recovered) => state = Some(recovered),
+                            Err(e) => {
+                                eprintln!(
+                                    "ERROR: Could not recreate the GPU device, retrying: {}",
+                                    e
+                                );
+                                std::thread::sleep(DEVICE_RETRY_DELAY);
+                            }
+                        }
+                        return;
+                    }
+                };
                 state.update();
                 let render_res = state.render();
                 match render_res {
-                    Ok(_) => {}
+                    Ok(_) => {
+                        surface_timing_out = false;
+                        if let Some(worker) = &skeleton_worker {
+                            if worker.is_due(state.frame()) {
+                                if let Some(field) = state.read_env() {
+                                    worker.send(state.frame(), field);
+                                }
+                            }
+                        }
+                        if let Some(scenario) = &scenario {
+                            if scenario.is_finished(state.frame()) {
+                                finish_network(scenario, state, world_size);
+                                elwt.exit();
+                            }
+                        }
+                    }
                     Err(wgpu::SurfaceError::Lost) => {
                         eprintln!("ERROR: Swap chain lost, recreating");
                         state.resize(state.window_size);
                     }
+                    // The window changed under the surface; reconfigure and try again next frame
+                    Err(wgpu::SurfaceError::Outdated) => {
+                        state.resize(state.window_size);
+                    }
+                    // Typically the window is hidden or the compositor is busy, skip the frame
+                    Err(wgpu::SurfaceError::Timeout) => {
+                        if !surface_timing_out {
+                            eprintln!("Surface timed out, skipping frames until it recovers");
+                            surface_timing_out = true;
+                        }
+                    }
                     Err(wgpu::SurfaceError::OutOfMemory) => {
                         eprintln!("ERROR: Ran out of memory!");
                         elwt.exit();
                     }
-                    Err(e) => {
-                        eprintln!("{:?}", e);
-                    }
                 }
             }
             _ => (),
         })
         .unwrap();
 }
+
+/// Creates a `State` to replace one whose device was lost, resuming from what it left behind.
+/// `lost` is only handed over once the new state exists, so it survives failed attempts. The
+/// world keeps its size from startup, whatever the window has been resized to since.
+fn recover_device<'a>(
+    window: &'a winit::window::Window,
+    world_size: PhysicalSize<u32>,
+    config: &config::Config,
+    lost: &mut Option<gpu::LostState>,
+) -> Result<gpu::State<'a>, error::InitError> {
+    let mut state = pollster::block_on(gpu::State::new(window, world_size, config, None))?;
+    if let Some(lost) = lost.take() {
+        state.resume(lost);
+    }
+    Ok(state)
+}
+
+fn finish_network(
+    scenario: &network::NetworkScenario,
+    state: &gpu::State,
+    world_size: PhysicalSize<u32>,
+) {
+    let Some(field) = state.read_env() else {
+        eprintln!("ERROR: Could not read the trail field back from the GPU");
+        return;
+    };
+    match scenario.finish(&field, world_size.width, world_size.height) {
+        Ok(network) => {
+            let c = &network.comparison;
+            println!(
+                "Network: {} edges, length {:.1} ({:.2}x MST), {}/{} MST edges, {} component(s)",
+                network.edges.len(),
+                c.network_length,
+                c.network_length / c.mst_length,
+                c.mst_edges_covered,
+                c.mst_edges,
+                c.components
+            );
+        }
+        Err(e) => eprintln!("ERROR: Could not write network: {}", e),
+    }
+}
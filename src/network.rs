@@ -0,0 +1,626 @@
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::analysis::{self, SkeletonGraph};
+use crate::config::Config;
+use crate::environment::EnvCell;
+use crate::food::FoodSource;
+
+/// A food node of the transport network, in world cells
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub name: String,
+    pub x: f32,
+    pub y: f32,
+}
+
+/// A connection between two nodes, weighted by the trail strength along it
+#[derive(Clone, Debug)]
+pub struct Edge {
+    pub a: usize,
+    pub b: usize,
+    pub length: f32,
+    pub mean_trail: f32,
+}
+
+/// How the emerging network compares to the minimum spanning tree of the same nodes
+#[derive(Clone, Debug)]
+pub struct Comparison {
+    pub network_length: f32,
+    pub mst_length: f32,
+    pub mst_edges_covered: usize,
+    pub mst_edges: usize,
+    pub components: usize,
+}
+
+pub struct TrailNetwork {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+    pub mst: Vec<Edge>,
+    pub comparison: Comparison,
+}
+
+/// Physarum transport-network scenario: food sources at the nodes, run for a fixed number of
+/// steps, then extract the trail network between them
+pub struct NetworkScenario {
+    nodes: Vec<Node>,
+    steps: u64,
+    node_radius: f32,
+    threshold: f32,
+    out: PathBuf,
+}
+
+/// Fraction of the world left empty around the nodes on each side
+const WORLD_MARGIN: f32 = 0.1;
+
+impl NetworkScenario {
+    pub fn from_config(config: &Config, width: u32, height: u32) -> io::Result<Option<Self>> {
+        let Some(path) = &config.network_nodes else {
+            return Ok(None);
+        };
+        let nodes = load_nodes(path, width, height)?;
+        if nodes.len() < 2 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} needs at least two nodes", path.display()),
+            ));
+        }
+        Ok(Some(Self {
+            nodes,
+            steps: config.network_steps,
+            node_radius: config.network_node_radius,
+            threshold: config.network_threshold,
+            out: config.network_out.clone(),
+        }))
+    }
+
+    pub fn food_sources(&self, rate: f32) -> impl Iterator<Item = FoodSource> + '_ {
+        self.nodes.iter().map(move |node| FoodSource::Disc {
+            x: node.x as u32,
+            y: node.y as u32,
+            radius: self.node_radius,
+            rate,
+        })
+    }
+
+    pub fn is_finished(&self, frame: u64) -> bool {
+        frame >= self.steps
+    }
+
+    /// Extracts the network from the final field and writes `<out>.graphml` and `<out>.json`
+    pub fn finish(&self, field: &[EnvCell], width: u32, height: u32) -> io::Result<TrailNetwork> {
+        let network = TrailNetwork::extract(
+            &self.nodes,
+            field,
+            width,
+            height,
+            self.node_radius,
+            self.threshold,
+        );
+        std::fs::write(self.out.with_extension("graphml"), network.to_graphml())?;
+        std::fs::write(self.out.with_extension("json"), network.to_json())?;
+        Ok(network)
+    }
+}
+
+/// Reads `x,y` or `name,x,y` rows (a non-numeric first row is taken as a header) and scales
+/// them uniformly into the world, so e.g. longitude/latitude pairs keep their aspect ratio
+pub fn load_nodes(path: &Path, width: u32, height: u32) -> io::Result<Vec<Node>> {
+    let text = std::fs::read_to_string(path)?;
+    parse_nodes(&text, path, width, height)
+}
+
+/// Parses the contents of the file at `path`, which is only used in error messages
+fn parse_nodes(text: &str, path: &Path, width: u32, height: u32) -> io::Result<Vec<Node>> {
+    let mut raw = Vec::new();
+    let mut first_row = true;
+    for (line_num, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let may_be_header = std::mem::replace(&mut first_row, false);
+        let cols: Vec<&str> = line.split(',').map(str::trim).collect();
+        let (name, x, y) = match cols.as_slice() {
+            [x, y] => (format!("n{}", raw.len()), x, y),
+            [name, x, y, ..] => (name.to_string(), x, y),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "{}:{}: expected `x,y` or `name,x,y`",
+                        path.display(),
+                        line_num + 1
+                    ),
+                ))
+            }
+        };
+        match (x.parse::<f32>(), y.parse::<f32>()) {
+            (Ok(x), Ok(y)) => raw.push(Node { name, x, y }),
+            _ if may_be_header => continue,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}:{}: invalid coordinates", path.display(), line_num + 1),
+                ))
+            }
+        }
+    }
+
+    let (min_x, max_x) = raw.iter().fold((f32::MAX, f32::MIN), |(lo, hi), n| {
+        (lo.min(n.x), hi.max(n.x))
+    });
+    let (min_y, max_y) = raw.iter().fold((f32::MAX, f32::MIN), |(lo, hi), n| {
+        (lo.min(n.y), hi.max(n.y))
+    });
+    let usable_w = width as f32 * (1.0 - 2.0 * WORLD_MARGIN);
+    let usable_h = height as f32 * (1.0 - 2.0 * WORLD_MARGIN);
+    let scale = (usable_w / (max_x - min_x).max(f32::EPSILON))
+        .min(usable_h / (max_y - min_y).max(f32::EPSILON));
+    // Centre the scaled bounding box in the world
+    let offset_x = (width as f32 - (max_x - min_x) * scale) / 2.0;
+    let offset_y = (height as f32 - (max_y - min_y) * scale) / 2.0;
+    Ok(raw
+        .into_iter()
+        .map(|n| Node {
+            name: n.name,
+            x: (n.x - min_x) * scale + offset_x,
+            y: (n.y - min_y) * scale + offset_y,
+        })
+        .collect())
+}
+
+impl TrailNetwork {
+    /// Skeletonises the cells with trail above `threshold` and takes the skeleton as the
+    /// network, so curved tubes and junctions away from the nodes are kept. Skeleton nodes
+    /// within `node_radius` of a food node are merged into it, the remaining junctions are
+    /// kept as Steiner nodes after the food nodes, and dead-end branches are pruned.
+    pub fn extract(
+        nodes: &[Node],
+        field: &[EnvCell],
+        width: u32,
+        height: u32,
+        node_radius: f32,
+        threshold: f32,
+    ) -> Self {
+        let skeleton = analysis::skeletonize(&analysis::threshold(field, threshold), width, height);
+        // Split every branch where it enters a food disc, so the disc becomes a node
+        let anchors: Vec<bool> = (0..width * height)
+            .map(|i| {
+                let (x, y) = ((i % width) as f32, (i / width) as f32);
+                nodes.iter().any(|n| distance_to(n, x, y) <= node_radius)
+            })
+            .collect();
+        let graph = SkeletonGraph::anchored(&skeleton, &anchors, field, width, height);
+
+        // Snap skeleton nodes to the closest food node in range, or keep them as junctions
+        let mut network_nodes = nodes.to_vec();
+        let snapped: Vec<usize> = graph
+            .nodes
+            .iter()
+            .map(|s| {
+                let closest = (0..nodes.len())
+                    .map(|k| (distance_to(&nodes[k], s.x, s.y), k))
+                    .min_by(|x, y| x.0.total_cmp(&y.0));
+                match closest {
+                    Some((d, k)) if d <= node_radius => k,
+                    _ => {
+                        network_nodes.push(Node {
+                            name: String::new(),
+                            x: s.x,
+                            y: s.y,
+                        });
+                        network_nodes.len() - 1
+                    }
+                }
+            })
+            .collect();
+        let mut edges: Vec<Edge> = graph
+            .edges
+            .iter()
+            .filter(|e| snapped[e.a] != snapped[e.b])
+            .map(|e| Edge {
+                a: snapped[e.a].min(snapped[e.b]),
+                b: snapped[e.a].max(snapped[e.b]),
+                length: e.length,
+                mean_trail: e.mean_intensity,
+            })
+            .collect();
+        let (mut network_nodes, edges) = prune_junctions(network_nodes, nodes.len(), &mut edges);
+        for (i, junction) in network_nodes[nodes.len()..].iter_mut().enumerate() {
+            junction.name = format!("junction{}", i);
+        }
+
+        let mst = minimum_spanning_tree(nodes);
+        let linked = linked_food_nodes(nodes.len(), network_nodes.len(), &edges);
+        let comparison = Comparison {
+            network_length: edges.iter().map(|e| e.length).sum(),
+            mst_length: mst.iter().map(|e| e.length).sum(),
+            mst_edges_covered: mst.iter().filter(|m| linked.contains(&(m.a, m.b))).count(),
+            mst_edges: mst.len(),
+            components: count_components(network_nodes.len(), &edges, nodes.len()),
+        };
+
+        Self {
+            nodes: network_nodes,
+            edges,
+            mst,
+            comparison,
+        }
+    }
+
+    pub fn to_graphml(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        out.push_str("  <key id=\"name\" for=\"node\" attr.name=\"name\" attr.type=\"string\"/>\n");
+        out.push_str("  <key id=\"x\" for=\"node\" attr.name=\"x\" attr.type=\"double\"/>\n");
+        out.push_str("  <key id=\"y\" for=\"node\" attr.name=\"y\" attr.type=\"double\"/>\n");
+        out.push_str(
+            "  <key id=\"length\" for=\"edge\" attr.name=\"length\" attr.type=\"double\"/>\n",
+        );
+        out.push_str(
+            "  <key id=\"weight\" for=\"edge\" attr.name=\"weight\" attr.type=\"double\"/>\n",
+        );
+        out.push_str("  <graph id=\"trail_network\" edgedefault=\"undirected\">\n");
+        for (i, node) in self.nodes.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "    <node id=\"n{}\"><data key=\"name\">{}</data><data key=\"x\">{}</data><data key=\"y\">{}</data></node>",
+                i,
+                xml_escape(&node.name),
+                node.x,
+                node.y
+            );
+        }
+        for edge in &self.edges {
+            let _ = writeln!(
+                out,
+                "    <edge source=\"n{}\" target=\"n{}\"><data key=\"length\">{}</data><data key=\"weight\">{}</data></edge>",
+                edge.a,
+                edge.b,
+                edge.length,
+                edge.mean_trail
+            );
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let edge_json = |e: &Edge| {
+            format!(
+                "{{\"source\": {}, \"target\": {}, \"length\": {}, \"weight\": {}}}",
+                e.a, e.b, e.length, e.mean_trail
+            )
+        };
+        let nodes = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| {
+                format!(
+                    "{{\"id\": {}, \"name\": {}, \"x\": {}, \"y\": {}}}",
+                    i,
+                    json_string(&n.name),
+                    n.x,
+                    n.y
+                )
+            })
+            .collect::<Vec<_>>();
+        let edges = self.edges.iter().map(edge_json).collect::<Vec<_>>();
+        let mst = self.mst.iter().map(edge_json).collect::<Vec<_>>();
+        let c = &self.comparison;
+        format!(
+            "{{\n  \"nodes\": [\n    {}\n  ],\n  \"edges\": [\n    {}\n  ],\n  \"mst\": [\n    {}\n  ],\n  \"comparison\": {{\"network_length\": {}, \"mst_length\": {}, \"mst_edges_covered\": {}, \"mst_edges\": {}, \"components\": {}}}\n}}\n",
+            nodes.join(",\n    "),
+            edges.join(",\n    "),
+            mst.join(",\n    "),
+            c.network_length,
+            c.mst_length,
+            c.mst_edges_covered,
+            c.mst_edges,
+            c.components
+        )
+    }
+}
+
+fn distance(a: &Node, b: &Node) -> f32 {
+    distance_to(a, b.x, b.y)
+}
+
+fn distance_to(node: &Node, x: f32, y: f32) -> f32 {
+    ((node.x - x).powi(2) + (node.y - y).powi(2)).sqrt()
+}
+
+/// Repeatedly drops junctions left with a single branch (dead ends) or none, and joins the two
+/// branches of a junction with exactly two into one edge. Food nodes, the first `food_count`,
+/// are always kept. Returns the surviving nodes and edges, renumbered.
+fn prune_junctions(
+    nodes: Vec<Node>,
+    food_count: usize,
+    edges: &mut Vec<Edge>,
+) -> (Vec<Node>, Vec<Edge>) {
+    let mut removed = vec![false; nodes.len()];
+    loop {
+        let mut degree = vec![0; nodes.len()];
+        for e in edges.iter() {
+            degree[e.a] += 1;
+            degree[e.b] += 1;
+        }
+        let Some(junction) = (food_count..nodes.len()).find(|&n| !removed[n] && degree[n] <= 2)
+        else {
+            break;
+        };
+        removed[junction] = true;
+        let (branches, rest): (Vec<Edge>, Vec<Edge>) = edges
+            .drain(..)
+            .partition(|e| e.a == junction || e.b == junction);
+        *edges = rest;
+        if let [first, second] = branches.as_slice() {
+            let other = |e: &Edge| if e.a == junction { e.b } else { e.a };
+            let (a, b) = (other(first), other(second));
+            let length = first.length + second.length;
+            if a != b && length > 0.0 {
+                edges.push(Edge {
+                    a: a.min(b),
+                    b: a.max(b),
+                    length,
+                    mean_trail: (first.mean_trail * first.length
+                        + second.mean_trail * second.length)
+                        / length,
+                });
+            }
+        }
+    }
+
+    let mut renumbered = vec![usize::MAX; nodes.len()];
+    let mut kept = Vec::new();
+    for (i, node) in nodes.into_iter().enumerate() {
+        if !removed[i] {
+            renumbered[i] = kept.len();
+            kept.push(node);
+        }
+    }
+    let edges = edges
+        .iter()
+        .map(|e| Edge {
+            a: renumbered[e.a],
+            b: renumbered[e.b],
+            ..*e
+        })
+        .collect();
+    (kept, edges)
+}
+
+/// Pairs of food nodes, lower index first, joined by a path passing only through junctions
+fn linked_food_nodes(
+    food_count: usize,
+    node_count: usize,
+    edges: &[Edge],
+) -> HashSet<(usize, usize)> {
+    let mut adjacent = vec![Vec::new(); node_count];
+    for e in edges {
+        adjacent[e.a].push(e.b);
+        adjacent[e.b].push(e.a);
+    }
+    let mut linked = HashSet::new();
+    for start in 0..food_count {
+        let mut seen = vec![false; node_count];
+        seen[start] = true;
+        let mut stack = vec![start];
+        while let Some(n) = stack.pop() {
+            for &next in &adjacent[n] {
+                if seen[next] {
+                    continue;
+                }
+                seen[next] = true;
+                if next < food_count {
+                    linked.insert((start.min(next), start.max(next)));
+                } else {
+                    stack.push(next);
+                }
+            }
+        }
+    }
+    linked
+}
+
+/// Kruskal's algorithm on the complete Euclidean graph of the nodes
+fn minimum_spanning_tree(nodes: &[Node]) -> Vec<Edge> {
+    let mut candidates = Vec::new();
+    for a in 0..nodes.len() {
+        for b in (a + 1)..nodes.len() {
+            candidates.push((distance(&nodes[a], &nodes[b]), a, b));
+        }
+    }
+    candidates.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+    let mut parent: Vec<usize> = (0..nodes.len()).collect();
+    let mut tree = Vec::new();
+    for (length, a, b) in candidates {
+        let (root_a, root_b) = (find_root(&mut parent, a), find_root(&mut parent, b));
+        if root_a != root_b {
+            parent[root_a] = root_b;
+            tree.push(Edge {
+                a,
+                b,
+                length,
+                mean_trail: 0.0,
+            });
+        }
+    }
+    tree
+}
+
+/// Separate pieces of the network holding at least one of the first `food_count` nodes
+fn count_components(node_count: usize, edges: &[Edge], food_count: usize) -> usize {
+    let mut parent: Vec<usize> = (0..node_count).collect();
+    for edge in edges {
+        let (root_a, root_b) = (
+            find_root(&mut parent, edge.a),
+            find_root(&mut parent, edge.b),
+        );
+        parent[root_a] = root_b;
+    }
+    let roots: HashSet<usize> = (0..food_count).map(|n| find_root(&mut parent, n)).collect();
+    roots.len()
+}
+
+fn find_root(parent: &mut [usize], mut n: usize) -> usize {
+    while parent[n] != n {
+        parent[n] = parent[parent[n]];
+        n = parent[n];
+    }
+    n
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(x: f32, y: f32) -> Node {
+        Node {
+            name: String::new(),
+            x,
+            y,
+        }
+    }
+
+    fn edge(a: usize, b: usize, length: f32, mean_trail: f32) -> Edge {
+        Edge {
+            a,
+            b,
+            length,
+            mean_trail,
+        }
+    }
+
+    fn parse(text: &str) -> io::Result<Vec<Node>> {
+        parse_nodes(text, Path::new("nodes.csv"), 100, 100)
+    }
+
+    #[test]
+    fn nodes_are_scaled_into_the_world() {
+        let nodes = parse("name,lon,lat\n# a comment\ntokyo,0,0\n10,0\n").unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].name, "tokyo");
+        assert_eq!(nodes[1].name, "n1");
+        // The 10 cell span fills the world less its margins, centred vertically
+        assert_eq!((nodes[0].x, nodes[0].y), (10.0, 50.0));
+        assert_eq!((nodes[1].x, nodes[1].y), (90.0, 50.0));
+    }
+
+    #[test]
+    fn only_the_first_row_may_be_a_header() {
+        let err = parse("x,y\nfar,away\n0,0\n1,1\n").err().unwrap();
+        assert!(err.to_string().starts_with("nodes.csv:2:"), "{}", err);
+        let err = parse("0,0\n1,one\n").err().unwrap();
+        assert!(err.to_string().starts_with("nodes.csv:2:"), "{}", err);
+    }
+
+    #[test]
+    fn rows_need_two_coordinates() {
+        let err = parse("0,0\n5\n").err().unwrap();
+        assert!(err.to_string().starts_with("nodes.csv:2:"), "{}", err);
+    }
+
+    #[test]
+    fn spanning_tree_takes_the_shortest_links() {
+        let nodes = [
+            node(0.0, 0.0),
+            node(3.0, 0.0),
+            node(0.0, 4.0),
+            node(3.0, 4.0),
+        ];
+        let mst = minimum_spanning_tree(&nodes);
+        let mut links: Vec<(usize, usize)> = mst.iter().map(|e| (e.a, e.b)).collect();
+        links.sort();
+        assert_eq!(links, [(0, 1), (0, 2), (2, 3)]);
+        assert_eq!(mst.iter().map(|e| e.length).sum::<f32>(), 10.0);
+    }
+
+    #[test]
+    fn pruning_drops_dead_ends_and_joins_pass_through_junctions() {
+        // Food nodes 0 and 1, junction 2 between them and a dead end at junction 3
+        let nodes = vec![
+            node(0.0, 0.0),
+            node(4.0, 0.0),
+            node(2.0, 0.0),
+            node(2.0, 2.0),
+        ];
+        let mut edges = vec![
+            edge(0, 2, 1.0, 2.0),
+            edge(1, 2, 3.0, 6.0),
+            edge(2, 3, 2.0, 1.0),
+        ];
+        let (kept, edges) = prune_junctions(nodes, 2, &mut edges);
+        assert_eq!(kept.len(), 2);
+        assert_eq!(edges.len(), 1);
+        assert_eq!((edges[0].a, edges[0].b), (0, 1));
+        assert_eq!(edges[0].length, 4.0);
+        // Weighted by the length of each branch
+        assert_eq!(edges[0].mean_trail, 5.0);
+    }
+
+    #[test]
+    fn pruning_keeps_real_junctions_and_renumbers_them() {
+        // A dead-end junction 3 before junction 4, which joins food nodes 0, 1 and 2
+        let nodes = vec![
+            node(0.0, 0.0),
+            node(2.0, 0.0),
+            node(1.0, 2.0),
+            node(5.0, 5.0),
+            node(1.0, 1.0),
+        ];
+        let mut edges = vec![
+            edge(0, 4, 1.0, 1.0),
+            edge(1, 4, 1.0, 1.0),
+            edge(2, 4, 1.0, 1.0),
+            edge(3, 4, 1.0, 1.0),
+        ];
+        let (kept, edges) = prune_junctions(nodes, 3, &mut edges);
+        assert_eq!(kept.len(), 4);
+        assert_eq!((kept[3].x, kept[3].y), (1.0, 1.0));
+        let mut links: Vec<(usize, usize)> = edges.iter().map(|e| (e.a, e.b)).collect();
+        links.sort();
+        assert_eq!(links, [(0, 3), (1, 3), (2, 3)]);
+    }
+
+    #[test]
+    fn food_nodes_link_through_junctions_only() {
+        // 0 - junction 3 - 1 - 2: 0 and 2 only meet by passing through food node 1
+        let edges = [
+            edge(0, 3, 1.0, 1.0),
+            edge(3, 1, 1.0, 1.0),
+            edge(1, 2, 1.0, 1.0),
+        ];
+        let linked = linked_food_nodes(3, 4, &edges);
+        assert_eq!(linked, HashSet::from([(0, 1), (1, 2)]));
+    }
+}
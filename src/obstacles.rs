@@ -0,0 +1,62 @@
+use std::path::Path;
+
+/// Static wall layout of the world, one byte per cell (255 = wall, 0 = free)
+pub struct ObstacleMask {
+    width: u32,
+    height: u32,
+    cells: Vec<u8>,
+}
+
+impl ObstacleMask {
+    /// A mask with no walls
+    pub fn empty(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![0; (width * height) as usize],
+        }
+    }
+
+    /// Loads a mask from an image, stretched to the world size with nearest sampling.
+    /// Pixels darker than mid-grey are walls.
+    pub fn from_image(path: &Path, width: u32, height: u32) -> Result<Self, image::ImageError> {
+        let img = image::open(path)?.into_luma8();
+        let mut cells = Vec::with_capacity((width * height) as usize);
+        // Image rows go top to bottom, world rows go bottom to top (texture v = 0 is at the bottom of the plane)
+        for y in 0..height {
+            let img_y = ((height - 1 - y) as u64 * img.height() as u64 / height as u64) as u32;
+            for x in 0..width {
+                let img_x = (x as u64 * img.width() as u64 / width as u64) as u32;
+                let luma = img.get_pixel(img_x, img_y).0[0];
+                cells.push(if luma < 128 { 255 } else { 0 });
+            }
+        }
+        Ok(Self {
+            width,
+            height,
+            cells,
+        })
+    }
+
+    pub fn texture_desc(&self) -> wgpu::TextureDescriptor<'static> {
+        wgpu::TextureDescriptor {
+            label: Some("Obstacle Mask Texture"),
+            size: wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        }
+    }
+
+    /// Row-major texel data matching `texture_desc`
+    pub fn data(&self) -> &[u8] {
+        &self.cells
+    }
+}
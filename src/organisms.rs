@@ -0,0 +1,303 @@
+//! Multi-cell organisms: runs of agents bonded by springs into rings or chains. The cells sense,
+//! steer and deposit like any agent; a constraint pass after the agent pass then pulls each body
+//! back into shape, with the springs shortening and relaxing rhythmically so the bodies pulse.
+
+use std::f32::consts::{PI, TAU};
+
+use rand::{thread_rng, Rng};
+
+use crate::config::Config;
+use crate::layout::shader_struct;
+
+/// Bonds each cell has room for, must match `MAX_BONDS` in the bond shader
+pub const MAX_BONDS: usize = 4;
+/// `Bond::other` of an unused bond, must match `NO_BOND` in the bond shader
+const NO_BOND: u32 = u32::MAX;
+/// Stiffness of the bonds skipping a cell, relative to `--bond-stiffness`, which resist bending
+const BENDING_STIFFNESS: f32 = 0.5;
+
+/// Body plan of an organism
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrganismShape {
+    /// A closed loop that contracts and relaxes as a whole, like a jellyfish bell
+    Ring,
+    /// An open strand with the contraction running along it as a wave
+    Chain,
+}
+
+shader_struct! {
+    #[repr(C)]
+    #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+    pub struct Bond {
+        // Agent slot at the other end, or NO_BOND
+        other: u32,
+        rest_length: f32,
+        // Fraction of the stretch corrected per constraint iteration
+        stiffness: f32,
+        // Offset into the pulse cycle, from 0 to 1
+        phase: f32,
+    }
+}
+
+impl Default for Bond {
+    fn default() -> Self {
+        Self {
+            other: NO_BOND,
+            rest_length: 0.0,
+            stiffness: 0.0,
+            phase: 0.0,
+        }
+    }
+}
+
+/// Organisms that fit in the starting population
+pub fn organism_count(config: &Config) -> u32 {
+    config.organisms.min(config.agents / config.organism_cells)
+}
+
+/// Agents bonded into organisms, which are the first slots of the population
+pub fn organism_cell_count(config: &Config) -> u32 {
+    organism_count(config) * config.organism_cells
+}
+
+/// Starting position and heading of every organism cell, organism by organism. Each body faces
+/// one random way, so its cells set off together.
+pub fn organism_cells(config: &Config) -> Vec<([f32; 2], f32)> {
+    let mut rng = thread_rng();
+    let n = config.organism_cells;
+    let mut cells = Vec::with_capacity(organism_cell_count(config) as usize);
+    for _ in 0..organism_count(config) {
+        let centre = [rng.gen_range(300.0..700.0), rng.gen_range(300.0..700.0)];
+        let heading: f32 = rng.gen_range(0.0..TAU);
+        for i in 0..n {
+            let offset = match config.organism_shape {
+                OrganismShape::Ring => {
+                    let radius = ring_radius(config);
+                    let angle = TAU * i as f32 / n as f32;
+                    [radius * angle.cos(), radius * angle.sin()]
+                }
+                // Trailing behind its first cell
+                OrganismShape::Chain => {
+                    let behind = -(i as f32) * config.bond_length;
+                    [behind * heading.cos(), behind * heading.sin()]
+                }
+            };
+            cells.push(([centre[0] + offset[0], centre[1] + offset[1]], heading));
+        }
+    }
+    cells
+}
+
+/// Radius of a ring whose neighbouring cells are `--bond-length` apart
+fn ring_radius(config: &Config) -> f32 {
+    config.bond_length / (2.0 * (PI / config.organism_cells as f32).sin())
+}
+
+impl Bond {
+    /// `MAX_BONDS` bonds per organism cell: to the next and previous cells, then to the cells
+    /// one further along. Always holds at least one cell, so the buffer is never empty.
+    pub fn buf_contents(config: &Config) -> Vec<Bond> {
+        let n = config.organism_cells;
+        let cell_count = organism_cell_count(config);
+        let mut bonds = vec![Bond::default(); MAX_BONDS * cell_count.max(1) as usize];
+        for cell in 0..cell_count {
+            let first = cell - cell % n;
+            let i = cell % n;
+            let mut others = Vec::with_capacity(MAX_BONDS);
+            for step in [1, 2] {
+                let stiffness = if step == 1 {
+                    config.bond_stiffness
+                } else {
+                    BENDING_STIFFNESS * config.bond_stiffness
+                };
+                let (ahead, behind, rest_length, phase) = match config.organism_shape {
+                    // Every bond of a ring pulses together
+                    OrganismShape::Ring => (
+                        Some((i + step) % n),
+                        Some((i + n - step) % n),
+                        2.0 * ring_radius(config) * (step as f32 * PI / n as f32).sin(),
+                        0.0,
+                    ),
+                    OrganismShape::Chain => (
+                        Some(i + step).filter(|&j| j < n),
+                        i.checked_sub(step),
+                        step as f32 * config.bond_length,
+                        i as f32 / n as f32,
+                    ),
+                };
+                for j in [ahead, behind].into_iter().flatten() {
+                    // Small rings reach the same cell both ways round
+                    if j != i && !others.iter().any(|b: &Bond| b.other == first + j) {
+                        others.push(Bond {
+                            other: first + j,
+                            rest_length,
+                            stiffness,
+                            phase,
+                        });
+                    }
+                }
+            }
+            let start = MAX_BONDS * cell as usize;
+            bonds[start..start + others.len()].copy_from_slice(&others);
+        }
+        bonds
+    }
+
+    pub fn buf_init_desc(bonds: &[Bond]) -> wgpu::util::BufferInitDescriptor<'_> {
+        wgpu::util::BufferInitDescriptor {
+            label: Some("Bond Buffer"),
+            contents: bytemuck::cast_slice(bonds),
+            usage: wgpu::BufferUsages::STORAGE,
+        }
+    }
+
+    /// Per organism cell, its position and heading as staged by one constraint pass for the
+    /// apply pass to copy back
+    pub fn staging_buf_desc(cell_count: u32) -> wgpu::BufferDescriptor<'static> {
+        wgpu::BufferDescriptor {
+            label: Some("Bond Staging Buffer"),
+            size: cell_count.max(1) as u64 * std::mem::size_of::<[f32; 4]>() as u64,
+            mapped_at_creation: false,
+            usage: wgpu::BufferUsages::STORAGE,
+        }
+    }
+
+    pub fn compute_shader_desc() -> wgpu::ShaderModuleDescriptor<'static> {
+        wgpu::ShaderModuleDescriptor {
+            label: Some("Bond Compute Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shader_compute_bonds.wgsl").into()),
+        }
+    }
+
+    pub fn bind_layout_desc() -> wgpu::BindGroupLayoutDescriptor<'static> {
+        wgpu::BindGroupLayoutDescriptor {
+            label: Some("Bond Compute Bind Group Layout"),
+            entries: &[
+                // Agent Buffer: the agents this step's agent pass wrote, reshaped in place
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Bond Buffer: the springs of each organism cell
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Bond Staging Buffer: written by the pulse and solve passes, read by apply
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Agent Texture: the bonds are drawn over the agents
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: wgpu::TextureFormat::Rgba8Unorm,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                // Obstacle Mask: cells are not pulled into walls
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    /// Bonds of one organism of `cells` cells, cell by cell, without the unused ones
+    fn bonds(shape: &str, cells: u32) -> Vec<Vec<Bond>> {
+        let cells = cells.to_string();
+        let config = Config::parse_from([
+            "slime",
+            "--organisms",
+            "1",
+            "--organism-cells",
+            &cells,
+            "--organism-shape",
+            shape,
+            "--bond-length",
+            "2",
+        ]);
+        Bond::buf_contents(&config)
+            .chunks(MAX_BONDS)
+            .map(|cell| {
+                cell.iter()
+                    .copied()
+                    .filter(|b| b.other != NO_BOND)
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn others(bonds: &[Bond]) -> Vec<u32> {
+        bonds.iter().map(|b| b.other).collect()
+    }
+
+    #[test]
+    fn small_rings_bond_each_cell_once() {
+        for cell in bonds("ring", 3) {
+            assert_eq!(cell.len(), 2);
+        }
+        for (i, cell) in bonds("ring", 4).iter().enumerate() {
+            let i = i as u32;
+            assert_eq!(others(cell), [(i + 1) % 4, (i + 3) % 4, (i + 2) % 4]);
+        }
+    }
+
+    #[test]
+    fn chain_ends_lack_the_bonds_past_them() {
+        let chain = bonds("chain", 4);
+        assert_eq!(others(&chain[0]), [1, 2]);
+        assert_eq!(others(&chain[1]), [2, 0, 3]);
+        assert_eq!(others(&chain[2]), [3, 1, 0]);
+        assert_eq!(others(&chain[3]), [2, 1]);
+    }
+
+    #[test]
+    fn bending_bonds_span_two_cells() {
+        let chain = bonds("chain", 4);
+        assert_eq!(chain[1][0].rest_length, 2.0);
+        assert_eq!(chain[1][2].rest_length, 4.0);
+        assert!(chain[1][2].stiffness < chain[1][0].stiffness);
+
+        // Across a square whose sides are the bond length
+        let ring = bonds("ring", 4);
+        assert!((ring[0][0].rest_length - 2.0).abs() < 1e-5);
+        assert!((ring[0][2].rest_length - 2.0 * 2f32.sqrt()).abs() < 1e-5);
+    }
+}
@@ -1,48 +1,178 @@
+use crate::config::Config;
+use crate::layout::shader_struct;
+
 pub struct Params {
     pub agent_compute_params: AgentComputeParams,
     pub agent_render_params: AgentRenderParams,
     pub env_compute_params: EnvComputeParams,
     pub env_render_params: EnvRenderParams,
+    pub hash_compute_params: HashComputeParams,
+    pub bond_compute_params: BondComputeParams,
+}
+
+shader_struct! {
+    #[repr(C)]
+    #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, Default)]
+    pub struct AgentComputeParams {
+        dimensions: [u32; 2],
+        food_weight: f32,
+        deposit_amount: f32,
+        bilinear_sensing: u32,
+        bilinear_deposit: u32,
+        sensor_count: u32,
+        sensor_radius: u32,
+        steering: u32,
+        random_ties: u32,
+        lifecycle: u32,
+        move_cost: f32,
+        food_energy: f32,
+        split_energy: f32,
+        max_age: u32,
+        mutation: f32,
+        predators: u32,
+        predator_gain: f32,
+        flee_weight: f32,
+        neighbour_radius: u32,
+        avoidance: f32,
+        flocking: f32,
+        separation: f32,
+        alignment: f32,
+        cohesion: f32,
+        species: u32,
+        friction: f32,
+        interaction_force: f32,
+        particle_life: u32,
+        organism_cells: u32,
+        _padding: u32,
+    }
+}
+
+shader_struct! {
+    #[repr(C)]
+    #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, Default)]
+    pub struct AgentRenderParams {
+        exposure: f32,
+        // Non-zero to tone map the texture, zero to display it as stored
+        tone_map: u32,
+        _padding: [u32; 2],
+    }
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, Default)]
-pub struct AgentComputeParams {
-    dimensions: [u32; 2],
+shader_struct! {
+    #[repr(C)]
+    #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, Default)]
+    pub struct EnvComputeParams {
+        dimensions: [u32; 2],
+        food_decay: f32,
+        kernel: u32,
+        sigma: f32,
+        diffusion_rate: f32,
+        _padding: [f32; 2],
+    }
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, Default)]
-pub struct AgentRenderParams {
-    a: f32
+shader_struct! {
+    #[repr(C)]
+    #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, Default)]
+    pub struct HashComputeParams {
+        dimensions: [u32; 2],
+        cell_size: u32,
+        _padding: u32,
+    }
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, Default)]
-pub struct EnvComputeParams {
-    dimensions: [u32; 2],
+shader_struct! {
+    #[repr(C)]
+    #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, Default)]
+    pub struct BondComputeParams {
+        dimensions: [u32; 2],
+        // Written every step, see `step_offset`
+        step: u32,
+        pulse_period: u32,
+        pulse_amplitude: f32,
+        pulse_thrust: f32,
+        _padding: [u32; 2],
+    }
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, Default)]
-pub struct EnvRenderParams {
-    a: f32
+shader_struct! {
+    #[repr(C)]
+    #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, Default)]
+    pub struct EnvRenderParams {
+        exposure: f32,
+        // Non-zero to tone map the texture, zero to display it as stored
+        tone_map: u32,
+        _padding: [u32; 2],
+    }
 }
 
 impl Params {
-    pub fn new(width: u32, height: u32) -> Self {
+    /// `hdr` is whether the sim textures are float, and so need tone mapping for display
+    pub fn new(width: u32, height: u32, config: &Config, hdr: bool) -> Self {
         Self {
             agent_compute_params: AgentComputeParams {
                 dimensions: [width, height],
+                food_weight: config.food_weight,
+                deposit_amount: config.deposit,
+                bilinear_sensing: config.bilinear_sensing as u32,
+                bilinear_deposit: config.bilinear_deposit as u32,
+                sensor_count: config.sensor_count,
+                sensor_radius: config.sensor_radius,
+                steering: config.steering.shader_id(),
+                random_ties: config.random_ties as u32,
+                lifecycle: config.lifecycle as u32,
+                move_cost: config.move_cost,
+                food_energy: config.food_energy,
+                split_energy: config.split_energy,
+                max_age: config.max_age,
+                mutation: config.mutation,
+                predators: (config.predators > 0) as u32,
+                predator_gain: config.predator_gain,
+                flee_weight: config.flee_weight,
+                neighbour_radius: config.neighbour_radius,
+                avoidance: config.avoidance,
+                flocking: config.flocking,
+                separation: config.separation,
+                alignment: config.alignment,
+                cohesion: config.cohesion,
+                species: config.species,
+                friction: config.friction,
+                interaction_force: config.interaction_force,
+                particle_life: config.particle_life as u32,
+                organism_cells: crate::organisms::organism_cell_count(config),
+                _padding: 0,
             },
+            // Agents only ever draw full-intensity markers
             agent_render_params: AgentRenderParams {
-                a: 0.0,
+                exposure: 1.0,
+                tone_map: 0,
+                _padding: [0; 2],
             },
             env_compute_params: EnvComputeParams {
                 dimensions: [width, height],
+                food_decay: config.food_decay,
+                kernel: config.diffusion.shader_id(),
+                sigma: config.sigma,
+                diffusion_rate: config.diffusion_rate,
+                _padding: [0.0; 2],
             },
             env_render_params: EnvRenderParams {
-                a: 0.0,
+                exposure: config.exposure,
+                tone_map: hdr as u32,
+                _padding: [0; 2],
+            },
+            hash_compute_params: HashComputeParams {
+                dimensions: [width, height],
+                cell_size: config.neighbour_radius,
+                _padding: 0,
+            },
+            bond_compute_params: BondComputeParams {
+                dimensions: [width, height],
+                step: 0,
+                pulse_period: config.pulse_period,
+                pulse_amplitude: config.pulse_amplitude,
+                pulse_thrust: config.pulse_thrust,
+                _padding: [0; 2],
             },
         }
     }
@@ -62,8 +192,19 @@ impl AgentComputeParams {
                         min_binding_size: None,
                     },
                     count: None,
-                }
-            ]
+                },
+                // Interaction Matrix: rewritten whenever it is edited at runtime
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
         }
     }
 }
@@ -87,3 +228,44 @@ impl EnvComputeParams {
         }
     }
 }
+
+impl HashComputeParams {
+    pub fn bind_layout_desc() -> wgpu::BindGroupLayoutDescriptor<'static> {
+        wgpu::BindGroupLayoutDescriptor {
+            label: Some("Hash Compute Uniform Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        }
+    }
+}
+
+impl BondComputeParams {
+    /// Byte offset of the step counter, which is rewritten before every step to drive the pulse
+    pub fn step_offset() -> u64 {
+        std::mem::offset_of!(Self, step) as u64
+    }
+
+    pub fn bind_layout_desc() -> wgpu::BindGroupLayoutDescriptor<'static> {
+        wgpu::BindGroupLayoutDescriptor {
+            label: Some("Bond Compute Uniform Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        }
+    }
+}
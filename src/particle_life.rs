@@ -0,0 +1,298 @@
+//! Particle life: every species attracts or repels every other within a radius of its own, and
+//! agents move by velocity and friction instead of following trails
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use rand::{thread_rng, Rng};
+use winit::keyboard::{Key, NamedKey};
+
+use crate::layout::shader_struct;
+
+/// Species the interaction matrix has room for, must match `MAX_SPECIES` in the agent shader
+pub const MAX_SPECIES: u32 = 8;
+/// Change to the selected attraction per key press
+const ATTRACTION_STEP: f32 = 0.1;
+/// Change to the selected radius per key press, in cells
+const RADIUS_STEP: f32 = 1.0;
+/// Smallest random radius, as a fraction of `--neighbour-radius`
+const MIN_RANDOM_RADIUS: f32 = 0.5;
+
+shader_struct! {
+    #[repr(C)]
+    #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+    pub struct InteractionMatrix {
+        // How species `a` reacts to species `b` at a * MAX_SPECIES + b: its attraction in x
+        // (negative repels) and the radius it reaches to in y. One vec4 per pair, as uniform
+        // arrays have a 16 byte stride.
+        pairs: [[f32; 4]; (MAX_SPECIES * MAX_SPECIES) as usize],
+    }
+}
+
+impl InteractionMatrix {
+    fn pair(&mut self, from: u32, to: u32) -> &mut [f32; 4] {
+        &mut self.pairs[(from * MAX_SPECIES + to) as usize]
+    }
+
+    pub fn buf_init_desc(&self) -> wgpu::util::BufferInitDescriptor<'_> {
+        wgpu::util::BufferInitDescriptor {
+            label: Some("Interaction Matrix Uniform"),
+            contents: bytemuck::bytes_of(self),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        }
+    }
+}
+
+/// The interaction matrix of a particle life run, with the pair the editing keys act on
+pub struct ParticleLife {
+    pub matrix: InteractionMatrix,
+    species: u32,
+    // Radii are kept within the spatial hash cell size, so neighbours are found in 3x3 cells
+    max_radius: f32,
+    selected: (u32, u32),
+}
+
+impl ParticleLife {
+    /// A random matrix for `species` species
+    pub fn new(species: u32, max_radius: f32) -> Self {
+        let mut particle_life = Self::empty(species, max_radius);
+        particle_life.randomise();
+        particle_life
+    }
+
+    /// No attraction between any pair, each reaching the largest radius
+    fn empty(species: u32, max_radius: f32) -> Self {
+        let mut matrix: InteractionMatrix = bytemuck::Zeroable::zeroed();
+        for from in 0..species {
+            for to in 0..species {
+                *matrix.pair(from, to) = [0.0, max_radius, 0.0, 0.0];
+            }
+        }
+        Self {
+            matrix,
+            species,
+            max_radius,
+            selected: (0, 0),
+        }
+    }
+
+    /// Reads `from,to,attraction[,radius]` rows, after an optional header row. Unlisted pairs
+    /// have no attraction and the largest radius.
+    pub fn load(path: &Path, species: u32, max_radius: f32) -> io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Self::parse(&text, path, species, max_radius)
+    }
+
+    /// Parses the contents of the file at `path`, which is only used in error messages
+    fn parse(text: &str, path: &Path, species: u32, max_radius: f32) -> io::Result<Self> {
+        let mut particle_life = Self::empty(species, max_radius);
+        let invalid = |line_num: usize, message: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:{}: {}", path.display(), line_num + 1, message),
+            )
+        };
+        let mut first_row = true;
+        for (line_num, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let may_be_header = std::mem::replace(&mut first_row, false);
+            let cols: Vec<&str> = line.split(',').map(str::trim).collect();
+            let (from, to, attraction, radius) = match cols.as_slice() {
+                [from, to, attraction] => (from, to, attraction, None),
+                [from, to, attraction, radius] => (from, to, attraction, Some(radius)),
+                _ => {
+                    return Err(invalid(
+                        line_num,
+                        "expected `from,to,attraction` or `from,to,attraction,radius`",
+                    ))
+                }
+            };
+            let parsed = (
+                from.parse::<u32>(),
+                to.parse::<u32>(),
+                attraction.parse::<f32>(),
+                radius.map_or(Ok(max_radius), |r| r.parse::<f32>()),
+            );
+            let (from, to, attraction, radius) = match parsed {
+                (Ok(from), Ok(to), Ok(attraction), Ok(radius)) => (from, to, attraction, radius),
+                _ if may_be_header => continue,
+                _ => return Err(invalid(line_num, "invalid interaction")),
+            };
+            if from >= species || to >= species {
+                return Err(invalid(
+                    line_num,
+                    &format!("species must be below --species ({})", species),
+                ));
+            }
+            *particle_life.matrix.pair(from, to) =
+                [attraction, radius.clamp(1.0, max_radius), 0.0, 0.0];
+        }
+        Ok(particle_life)
+    }
+
+    /// Draws every attraction from [-1, 1] and every radius from the upper half of the range
+    pub fn randomise(&mut self) {
+        let mut rng = thread_rng();
+        for from in 0..self.species {
+            for to in 0..self.species {
+                let attraction = rng.gen_range(-1.0..=1.0);
+                let radius = self.max_radius * rng.gen_range(MIN_RANDOM_RADIUS..=1.0);
+                *self.matrix.pair(from, to) = [attraction, radius, 0.0, 0.0];
+            }
+        }
+    }
+
+    /// Applies a matrix editing key: arrows select a pair, +/- change its attraction, [/] its
+    /// radius and R randomises everything. Returns whether the key was one of them.
+    pub fn handle_key(&mut self, key: &Key) -> bool {
+        let (from, to) = self.selected;
+        let last = self.species - 1;
+        match key {
+            Key::Named(NamedKey::ArrowUp) => self.selected.0 = from.saturating_sub(1),
+            Key::Named(NamedKey::ArrowDown) => self.selected.0 = (from + 1).min(last),
+            Key::Named(NamedKey::ArrowLeft) => self.selected.1 = to.saturating_sub(1),
+            Key::Named(NamedKey::ArrowRight) => self.selected.1 = (to + 1).min(last),
+            Key::Character(c) => match c.as_str() {
+                "+" | "=" => self.adjust(ATTRACTION_STEP, 0.0),
+                "-" => self.adjust(-ATTRACTION_STEP, 0.0),
+                "]" => self.adjust(0.0, RADIUS_STEP),
+                "[" => self.adjust(0.0, -RADIUS_STEP),
+                "r" | "R" => self.randomise(),
+                _ => return false,
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    fn adjust(&mut self, attraction: f32, radius: f32) {
+        let max_radius = self.max_radius;
+        let pair = self.matrix.pair(self.selected.0, self.selected.1);
+        pair[0] = (pair[0] + attraction).clamp(-1.0, 1.0);
+        pair[1] = (pair[1] + radius).clamp(1.0, max_radius);
+    }
+}
+
+/// The matrix as attraction@radius, rows reacting to columns, with the selected pair bracketed
+impl fmt::Display for ParticleLife {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Interactions (row reacting to column, attraction@radius):"
+        )?;
+        for from in 0..self.species {
+            for to in 0..self.species {
+                let [attraction, radius, ..] =
+                    self.matrix.pairs[(from * MAX_SPECIES + to) as usize];
+                let cell = format!("{:+.2}@{:.0}", attraction, radius);
+                if (from, to) == self.selected {
+                    write!(f, " [{:>8}]", cell)?;
+                } else {
+                    write!(f, "  {:>8} ", cell)?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_RADIUS: f32 = 10.0;
+
+    fn parse(text: &str) -> io::Result<ParticleLife> {
+        ParticleLife::parse(text, Path::new("matrix.csv"), 3, MAX_RADIUS)
+    }
+
+    fn character(c: &str) -> Key {
+        Key::Character(c.into())
+    }
+
+    #[test]
+    fn load_reads_rows_after_a_header() {
+        let mut particle_life =
+            parse("# a comment\nfrom,to,attraction,radius\n0,1,0.5\n2, 0, -0.25, 4\n").unwrap();
+        assert_eq!(
+            *particle_life.matrix.pair(0, 1),
+            [0.5, MAX_RADIUS, 0.0, 0.0]
+        );
+        assert_eq!(*particle_life.matrix.pair(2, 0), [-0.25, 4.0, 0.0, 0.0]);
+        // Unlisted pairs
+        assert_eq!(
+            *particle_life.matrix.pair(1, 1),
+            [0.0, MAX_RADIUS, 0.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn load_clamps_radii() {
+        let mut particle_life = parse("0,0,1,0.5\n1,1,1,99\n").unwrap();
+        assert_eq!(particle_life.matrix.pair(0, 0)[1], 1.0);
+        assert_eq!(particle_life.matrix.pair(1, 1)[1], MAX_RADIUS);
+    }
+
+    #[test]
+    fn only_the_first_row_may_be_a_header() {
+        let err = parse("from,to,attraction\nx,y,z\n0,1,0.5\n").err().unwrap();
+        assert!(err.to_string().starts_with("matrix.csv:2:"), "{}", err);
+        let err = parse("0,1,0.5\n0,1,strong\n").err().unwrap();
+        assert!(err.to_string().starts_with("matrix.csv:2:"), "{}", err);
+    }
+
+    #[test]
+    fn load_rejects_bad_rows() {
+        assert!(parse("0,1\n").is_err());
+        assert!(parse("0,1,0.5,1,2\n").is_err());
+        assert!(parse("0,3,0.5\n").is_err());
+    }
+
+    #[test]
+    fn arrows_move_the_selection_within_the_matrix() {
+        let mut particle_life = ParticleLife::empty(3, MAX_RADIUS);
+        assert!(particle_life.handle_key(&Key::Named(NamedKey::ArrowUp)));
+        assert!(particle_life.handle_key(&Key::Named(NamedKey::ArrowLeft)));
+        assert_eq!(particle_life.selected, (0, 0));
+        for _ in 0..4 {
+            particle_life.handle_key(&Key::Named(NamedKey::ArrowDown));
+        }
+        particle_life.handle_key(&Key::Named(NamedKey::ArrowRight));
+        assert_eq!(particle_life.selected, (2, 1));
+    }
+
+    #[test]
+    fn keys_edit_the_selected_pair() {
+        let mut particle_life = ParticleLife::empty(3, MAX_RADIUS);
+        particle_life.selected = (1, 2);
+        assert!(particle_life.handle_key(&character("+")));
+        assert!(particle_life.handle_key(&character("[")));
+        assert_eq!(
+            *particle_life.matrix.pair(1, 2),
+            [ATTRACTION_STEP, MAX_RADIUS - RADIUS_STEP, 0.0, 0.0]
+        );
+        assert_eq!(
+            *particle_life.matrix.pair(2, 1),
+            [0.0, MAX_RADIUS, 0.0, 0.0]
+        );
+        assert!(!particle_life.handle_key(&character("x")));
+        assert!(!particle_life.handle_key(&Key::Named(NamedKey::Enter)));
+    }
+
+    #[test]
+    fn adjust_keeps_the_pair_in_range() {
+        let mut particle_life = ParticleLife::empty(2, MAX_RADIUS);
+        particle_life.adjust(5.0, 5.0);
+        assert_eq!(
+            *particle_life.matrix.pair(0, 0),
+            [1.0, MAX_RADIUS, 0.0, 0.0]
+        );
+        particle_life.adjust(-5.0, -50.0);
+        assert_eq!(*particle_life.matrix.pair(0, 0), [-1.0, 1.0, 0.0, 0.0]);
+    }
+}
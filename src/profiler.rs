@@ -0,0 +1,214 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use crate::readback::ReadbackRing;
+
+/// Passes timed by the profiler, in the order they run within a frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProfiledPass {
+    TextureClear,
+    AgentCompute,
+    EnvCompute,
+    PlaneRender,
+}
+
+const PROFILED_PASSES: [ProfiledPass; 4] = [
+    ProfiledPass::TextureClear,
+    ProfiledPass::AgentCompute,
+    ProfiledPass::EnvCompute,
+    ProfiledPass::PlaneRender,
+];
+
+impl ProfiledPass {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::TextureClear => "texture clear",
+            Self::AgentCompute => "agent compute",
+            Self::EnvCompute => "env compute",
+            Self::PlaneRender => "plane render",
+        }
+    }
+
+    fn begin_index(self) -> u32 {
+        2 * PROFILED_PASSES.iter().position(|&p| p == self).unwrap() as u32
+    }
+}
+
+/// Number of frames the reported averages are taken over
+const AVERAGE_WINDOW: usize = 60;
+const READBACK_SLOTS: usize = 3;
+
+#[derive(Default)]
+struct RollingAverage {
+    samples: VecDeque<f64>,
+}
+
+impl RollingAverage {
+    fn push(&mut self, value: f64) {
+        if self.samples.len() == AVERAGE_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(value);
+    }
+
+    fn get(&self) -> Option<f64> {
+        (!self.samples.is_empty())
+            .then(|| self.samples.iter().sum::<f64>() / self.samples.len() as f64)
+    }
+}
+
+/// GPU timestamp queries around each pass, when the adapter supports them
+struct TimestampQueries {
+    query_set: wgpu::QuerySet,
+    buf_resolve: wgpu::Buffer,
+    readback: ReadbackRing,
+    // Nanoseconds per timestamp tick
+    period: f32,
+}
+
+/// Rolling per-pass GPU timings, with the CPU-side frame time as a fallback
+pub struct Profiler {
+    timestamps: Option<TimestampQueries>,
+    pass_ms: [RollingAverage; PROFILED_PASSES.len()],
+    frame_ms: RollingAverage,
+    last_frame: Option<Instant>,
+}
+
+impl Profiler {
+    /// The features to request for timing, out of those the adapter offers
+    pub fn features(adapter_features: wgpu::Features) -> wgpu::Features {
+        adapter_features & wgpu::Features::TIMESTAMP_QUERY
+    }
+
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let timestamps = device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| {
+                let count = 2 * PROFILED_PASSES.len() as u32;
+                let size = count as u64 * std::mem::size_of::<u64>() as u64;
+                TimestampQueries {
+                    query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                        label: Some("Profiler Query Set"),
+                        ty: wgpu::QueryType::Timestamp,
+                        count,
+                    }),
+                    buf_resolve: device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("Profiler Resolve Buffer"),
+                        size,
+                        usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                        mapped_at_creation: false,
+                    }),
+                    readback: ReadbackRing::new(
+                        device,
+                        "Profiler Readback Buffer",
+                        size,
+                        READBACK_SLOTS,
+                    ),
+                    period: queue.get_timestamp_period(),
+                }
+            });
+        if timestamps.is_none() {
+            println!("GPU timestamp queries unsupported, profiling CPU frame time only");
+        }
+        Self {
+            timestamps,
+            pass_ms: Default::default(),
+            frame_ms: RollingAverage::default(),
+            last_frame: None,
+        }
+    }
+
+    pub fn compute_timestamp_writes(
+        &self,
+        pass: ProfiledPass,
+    ) -> Option<wgpu::ComputePassTimestampWrites<'_>> {
+        self.timestamps
+            .as_ref()
+            .map(|t| wgpu::ComputePassTimestampWrites {
+                query_set: &t.query_set,
+                beginning_of_pass_write_index: Some(pass.begin_index()),
+                end_of_pass_write_index: Some(pass.begin_index() + 1),
+            })
+    }
+
+    pub fn render_timestamp_writes(
+        &self,
+        pass: ProfiledPass,
+    ) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        self.timestamps
+            .as_ref()
+            .map(|t| wgpu::RenderPassTimestampWrites {
+                query_set: &t.query_set,
+                beginning_of_pass_write_index: Some(pass.begin_index()),
+                end_of_pass_write_index: Some(pass.begin_index() + 1),
+            })
+    }
+
+    /// Times encoder commands outside of any pass, such as a texture clear
+    pub fn time_commands(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        pass: ProfiledPass,
+        commands: impl FnOnce(&mut wgpu::CommandEncoder),
+    ) {
+        match &self.timestamps {
+            Some(t) => {
+                encoder.write_timestamp(&t.query_set, pass.begin_index());
+                commands(encoder);
+                encoder.write_timestamp(&t.query_set, pass.begin_index() + 1);
+            }
+            None => commands(encoder),
+        }
+    }
+
+    /// Resolves this frame's timestamps into the readback ring; call once all passes are encoded
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder, frame: u64) {
+        if let Some(t) = &mut self.timestamps {
+            let count = 2 * PROFILED_PASSES.len() as u32;
+            encoder.resolve_query_set(&t.query_set, 0..count, &t.buf_resolve, 0);
+            t.readback.copy_from(encoder, &t.buf_resolve, frame);
+        }
+    }
+
+    /// Marks the end of a frame's CPU work; call after submitting the frame
+    pub fn end_frame(&mut self) {
+        let now = Instant::now();
+        if let Some(last) = self.last_frame {
+            self.frame_ms.push((now - last).as_secs_f64() * 1000.0);
+        }
+        self.last_frame = Some(now);
+        if let Some(t) = &mut self.timestamps {
+            t.readback.map_copied();
+        }
+    }
+
+    /// Folds finished timestamp readbacks into the averages without blocking
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        let Some(t) = &mut self.timestamps else {
+            return;
+        };
+        for (_, bytes) in t.readback.poll(device) {
+            let ticks: &[u64] = bytemuck::cast_slice(&bytes);
+            for i in 0..PROFILED_PASSES.len() {
+                let (begin, end) = (ticks[2 * i], ticks[2 * i + 1]);
+                let ns = end.saturating_sub(begin) as f64 * t.period as f64;
+                self.pass_ms[i].push(ns / 1.0e6);
+            }
+        }
+    }
+
+    /// One-line summary of the rolling averages, in milliseconds
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(ms) = self.frame_ms.get() {
+            parts.push(format!("frame {:.2}ms", ms));
+        }
+        for (pass, average) in PROFILED_PASSES.iter().zip(&self.pass_ms) {
+            if let Some(ms) = average.get() {
+                parts.push(format!("{} {:.3}ms", pass.name(), ms));
+            }
+        }
+        parts.join(" | ")
+    }
+}
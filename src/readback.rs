@@ -0,0 +1,92 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A ring of staging buffers for reading GPU results back without stalling.
+/// Each sample is copied into a free slot, mapped asynchronously, and collected by `poll`
+/// once the GPU has caught up. When every slot is still in flight the sample is dropped.
+pub struct ReadbackRing {
+    slots: Vec<Slot>,
+    next: usize,
+}
+
+struct Slot {
+    buffer: wgpu::Buffer,
+    state: SlotState,
+}
+
+enum SlotState {
+    Free,
+    Copied(u64),
+    Mapping(u64, Arc<AtomicBool>),
+}
+
+impl ReadbackRing {
+    pub fn new(device: &wgpu::Device, label: &str, size: u64, count: usize) -> Self {
+        let slots = (0..count)
+            .map(|_| Slot {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(label),
+                    size,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                state: SlotState::Free,
+            })
+            .collect();
+        Self { slots, next: 0 }
+    }
+
+    /// Records a copy of `src` tagged with `tag` into the next free slot.
+    /// Returns false if the sample was dropped because no slot is free.
+    pub fn copy_from(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        src: &wgpu::Buffer,
+        tag: u64,
+    ) -> bool {
+        let slot = &mut self.slots[self.next];
+        if !matches!(slot.state, SlotState::Free) {
+            return false;
+        }
+        encoder.copy_buffer_to_buffer(src, 0, &slot.buffer, 0, slot.buffer.size());
+        slot.state = SlotState::Copied(tag);
+        self.next = (self.next + 1) % self.slots.len();
+        true
+    }
+
+    /// Starts mapping every slot copied into since the last call.
+    /// Must be called after the encoder holding the copies has been submitted.
+    pub fn map_copied(&mut self) {
+        for slot in &mut self.slots {
+            if let SlotState::Copied(tag) = slot.state {
+                let ready = Arc::new(AtomicBool::new(false));
+                let ready_cb = ready.clone();
+                slot.buffer
+                    .slice(..)
+                    .map_async(wgpu::MapMode::Read, move |res| {
+                        if res.is_ok() {
+                            ready_cb.store(true, Ordering::Release);
+                        }
+                    });
+                slot.state = SlotState::Mapping(tag, ready);
+            }
+        }
+    }
+
+    /// Collects the samples whose mapping has finished, oldest first. Never blocks.
+    pub fn poll(&mut self, device: &wgpu::Device) -> Vec<(u64, Vec<u8>)> {
+        device.poll(wgpu::Maintain::Poll);
+        let mut done = Vec::new();
+        for slot in &mut self.slots {
+            if let SlotState::Mapping(tag, ready) = &slot.state {
+                if ready.load(Ordering::Acquire) {
+                    done.push((*tag, slot.buffer.slice(..).get_mapped_range().to_vec()));
+                    slot.buffer.unmap();
+                    slot.state = SlotState::Free;
+                }
+            }
+        }
+        done.sort_by_key(|(tag, _)| *tag);
+        done
+    }
+}
@@ -1,39 +1,423 @@
 struct Agent {
     position: vec2<f32>,
+    velocity: vec2<f32>,
     angle: f32,
     turn_speed: f32,
+    energy: f32,
+    age: u32,
+    // Zero for a free slot
+    alive: u32,
+    // Heritable traits, mutated when an agent splits
+    sensor_spread: f32,
+    sensor_distance: f32,
+    speed: f32,
+    turn_strength: f32,
+    species: u32,
 };
 
 struct EnvCell {
     pheromone_level: f32,
+    food_level: f32,
+    scent_level: f32,
 }
 
 struct Uniforms {
     dimensions: vec2<u32>,
+    food_weight: f32,
+    deposit_amount: f32,
+    // Non-zero to interpolate sensing and splat deposits, zero for whole cells
+    bilinear_sensing: u32,
+    bilinear_deposit: u32,
+    sensor_count: u32,
+    // Sensors sum a (2 * radius + 1)^2 square of cells
+    sensor_radius: u32,
+    steering: u32,
+    random_ties: u32,
+    // Non-zero to spend and gain energy, die and split
+    lifecycle: u32,
+    move_cost: f32,
+    food_energy: f32,
+    split_energy: f32,
+    // Zero for no limit
+    max_age: u32,
+    // Largest relative change to each trait on a split
+    mutation: f32,
+    // Non-zero when there are predators, so prey mark the occupancy grid and only prey eat food
+    predators: u32,
+    predator_gain: f32,
+    flee_weight: f32,
+    // Also the cell size of the spatial hash
+    neighbour_radius: u32,
+    // Zero when the spatial hash is not built
+    avoidance: f32,
+    // Fraction of steering from the flocking rules rather than the pheromone sensors
+    flocking: f32,
+    separation: f32,
+    alignment: f32,
+    cohesion: f32,
+    // Particle life species in use, out of MAX_SPECIES
+    species: u32,
+    // Fraction of its velocity a particle loses each step
+    friction: f32,
+    interaction_force: f32,
+    // Non-zero when particle_life_main moves the agents instead of compute_main
+    particle_life: u32,
+    // Agents in organisms, the first slots, which settle_main settles after the bond pass
+    organism_cells: u32,
+}
+
+// Must match InteractionMatrix. How species a reacts to species b is at a * MAX_SPECIES + b:
+// its attraction in x and interaction radius in y.
+struct Interactions {
+    pairs: array<vec4<f32>, 64>,
+}
+
+// Must match Agent::lifecycle_buf_contents. `slots` holds a stack of `free_count` free agent
+// slots, then from index arrayLength(&agent_src) the parents of this step's births.
+struct Lifecycle {
+    free_count: atomic<i32>,
+    birth_count: atomic<u32>,
+    slots: array<u32>,
+}
+
+// What an agent sees of the other agents within neighbour_radius
+struct Neighbourhood {
+    // Unit vectors away from each neighbour, weighted by how close it is
+    push: vec2<f32>,
+    heading_total: vec2<f32>,
+    position_total: vec2<f32>,
+    count: f32,
 }
 
 struct ComputeInput {
 @builtin(global_invocation_id) global_id: vec3<u32>,
 };
 
+// Deposits are summed as fixed point (amount * DEPOSIT_SCALE), so agents sharing a cell add up
+const DEPOSIT_SCALE: f32 = 4096.0;
+
+// Must match SteeringRule::shader_id
+const STEERING_DISCRETE: u32 = 0u;
+const STEERING_GRADIENT: u32 = 1u;
+// Must match MAX_SENSORS in agents.rs
+const MAX_SENSORS: u32 = 16u;
+// Must match SPECIES_* in agents.rs
+const SPECIES_PREY: u32 = 0u;
+const SPECIES_PREDATOR: u32 = 1u;
+const PI: f32 = 3.14159265;
+// Trait ranges mutation clamps to, matching TRAIT_RANGES in stats.rs, which also bounds the
+// starting values given on the command line
+const MAX_SENSOR_SPREAD: f32 = PI;
+const MAX_SENSOR_DISTANCE: f32 = 32.0;
+const MAX_SPEED: f32 = 4.0;
+const MAX_TURN_STRENGTH: f32 = 1.0;
+// Must match MAX_SPECIES in particle_life.rs
+const MAX_SPECIES: u32 = 8u;
+// Fraction of an interaction radius within which every pair of particles repels, so they keep
+// apart whatever the matrix says
+const PARTICLE_REPULSION_RANGE: f32 = 0.3;
+
 fn hash_2d(in: vec2<f32>) -> f32 {
     return fract(sin(dot(in, vec2(12.9898, 78.233))) * 43758.5453);
 }
 
+// Format of the env and agent textures, switched to rgba16float by hdr.rs when float storage
+// textures are available
+alias SimTexture = texture_storage_2d<rgba8unorm, write>;
+
 @group(0) @binding(0) var<storage, read> agent_src: array<Agent>;
 @group(0) @binding(1) var<storage, read_write> agent_dest: array<Agent>;
-@group(0) @binding(2) var agent_texture: texture_storage_2d<rgba8unorm, write>;
+@group(0) @binding(2) var agent_texture: SimTexture;
 @group(0) @binding(3) var<storage, read> env_src: array<EnvCell>;
-@group(0) @binding(4) var<storage, read_write> env_dest: array<EnvCell>;
+// The prey trail for every cell, then the predator scent for every cell
+@group(0) @binding(4) var<storage, read_write> deposits: array<atomic<u32>>;
+@group(0) @binding(5) var obstacle_mask: texture_2d<f32>;
+@group(0) @binding(6) var<storage, read_write> lifecycle: Lifecycle;
+// Per cell, one plus the index of a prey in it, or zero
+@group(0) @binding(7) var<storage, read_write> occupancy: array<atomic<u32>>;
+// Spatial hash of agent_src built by the hash shader: the agent count of every grid cell, then
+// where each cell's agents start in hash_sorted
+@group(0) @binding(8) var<storage, read> hash_cells: array<u32>;
+@group(0) @binding(9) var<storage, read> hash_sorted: array<u32>;
 
 @group(1) @binding(0) var<uniform> uniforms: Uniforms;
+@group(1) @binding(1) var<uniform> interactions: Interactions;
+
+fn cell_index(cell: vec2<u32>) -> u32 {
+    return cell.y * uniforms.dimensions.x + cell.x;
+}
+
+fn is_blocked(cell: vec2<u32>) -> bool {
+    return textureLoad(obstacle_mask, cell, 0).r > 0.5;
+}
+
+// Attractant level at a cell as seen by a species, with walls and out-of-bounds cells reading as
+// empty. Prey follow the trail and food and avoid predator scent; predators track the prey trail.
+fn sense(cell: vec2<i32>, species: u32) -> f32 {
+    let check = vec2<u32>(cell);
+    if (cell.x < 0
+        || cell.y < 0
+        || check.x >= uniforms.dimensions.x
+        || check.y >= uniforms.dimensions.y
+        || is_blocked(check)) {
+        return 0.0;
+    }
+    let env = env_src[cell_index(check)];
+    if (species == SPECIES_PREDATOR) {
+        return env.pheromone_level;
+    }
+    let attractant = env.pheromone_level + uniforms.food_weight * env.food_level;
+    return max(0.0, attractant - uniforms.flee_weight * env.scent_level);
+}
+
+// Attractant at a point, interpolated between the four nearest cell centres
+fn sense_bilinear(point: vec2<f32>, species: u32) -> f32 {
+    let corner = point - 0.5;
+    let base = vec2<i32>(floor(corner));
+    let t = fract(corner);
+    let bottom = mix(sense(base, species), sense(base + vec2<i32>(1, 0), species), t.x);
+    let top = mix(sense(base + vec2<i32>(0, 1), species), sense(base + vec2<i32>(1, 1), species), t.x);
+    return mix(bottom, top, t.y);
+}
+
+// Attractant at a whole-cell offset from a sensor origin, either interpolated or from the
+// cell the origin falls in
+fn sense_point(origin: vec2<f32>, offset: vec2<i32>, species: u32) -> f32 {
+    if (uniforms.bilinear_sensing != 0u) {
+        return sense_bilinear(origin + vec2<f32>(offset), species);
+    }
+    return sense(vec2<i32>(origin) + offset, species);
+}
+
+// Adds to the prey trail, or with `channel` one to the predator scent
+fn deposit(cell: vec2<i32>, amount: f32, channel: u32) {
+    let check = vec2<u32>(cell);
+    if (cell.x < 0
+        || cell.y < 0
+        || check.x >= uniforms.dimensions.x
+        || check.y >= uniforms.dimensions.y) {
+        return;
+    }
+    let index = cell_index(check) + channel * uniforms.dimensions.x * uniforms.dimensions.y;
+    atomicAdd(&deposits[index], u32(amount * DEPOSIT_SCALE));
+}
+
+// Deposits a species' trail or scent at a point, either split between the four nearest cell
+// centres by bilinear weights or all into the cell the point falls in
+fn deposit_at(point: vec2<f32>, species: u32) {
+    let amount = uniforms.deposit_amount;
+    let channel = u32(species == SPECIES_PREDATOR);
+    if (uniforms.bilinear_deposit == 0u) {
+        deposit(vec2<i32>(point), amount, channel);
+        return;
+    }
+    let corner = point - 0.5;
+    let base = vec2<i32>(floor(corner));
+    let t = fract(corner);
+    deposit(base, amount * (1.0 - t.x) * (1.0 - t.y), channel);
+    deposit(base + vec2<i32>(1, 0), amount * t.x * (1.0 - t.y), channel);
+    deposit(base + vec2<i32>(0, 1), amount * (1.0 - t.x) * t.y, channel);
+    deposit(base + vec2<i32>(1, 1), amount * t.x * t.y, channel);
+}
+
+// Sensor angle relative to the agent's heading, evenly spread across an arc of +-spread
+fn sensor_angle(sensor: u32, spread: f32) -> f32 {
+    if (uniforms.sensor_count < 2u) {
+        return 0.0;
+    }
+    let t = f32(sensor) / f32(uniforms.sensor_count - 1u);
+    return spread * (2.0 * t - 1.0);
+}
+
+// Attractant summed over the footprint of a sensor `distance` cells away
+fn read_sensor(position: vec2<f32>, angle: f32, distance: f32, species: u32) -> f32 {
+    let origin = position + distance * vec2<f32>(cos(angle), sin(angle));
+    let radius = i32(uniforms.sensor_radius);
+    var total = 0.0;
+    for (var i: i32 = -radius; i <= radius; i++) {
+        for (var j: i32 = -radius; j <= radius; j++) {
+            total += sense_point(origin, vec2<i32>(i, j), species);
+        }
+    }
+    return total;
+}
+
+// Whether sensor `a` wins a tie against sensor `b`: the one nearer straight ahead, then the
+// right-hand one
+fn is_preferred(a: f32, b: f32) -> bool {
+    return abs(a) < abs(b) || (abs(a) == abs(b) && a > b);
+}
+
+// Turn for one step given the agent's position, heading and sensing traits
+fn steer(agent: Agent) -> f32 {
+    let position = agent.position;
+    let heading = agent.angle;
+    let count = min(uniforms.sensor_count, MAX_SENSORS);
+    var best_angle = 0.0;
+    var best_reading = -1.0;
+    var ties = 0.0;
+    var weighted_angle = 0.0;
+    var total_reading = 0.0;
+    for (var sensor = 0u; sensor < count; sensor++) {
+        let angle = sensor_angle(sensor, agent.sensor_spread);
+        let reading = read_sensor(position, heading + angle, agent.sensor_distance, agent.species);
+        weighted_angle += reading * angle;
+        total_reading += reading;
+
+        if (reading > best_reading) {
+            best_angle = angle;
+            best_reading = reading;
+            ties = 1.0;
+        } else if (reading == best_reading) {
+            ties += 1.0;
+            if (uniforms.random_ties != 0u) {
+                // Reservoir sampling keeps each tied sensor with equal probability
+                if (hash_2d(position + vec2<f32>(f32(sensor), heading)) * ties < 1.0) {
+                    best_angle = angle;
+                }
+            } else if (is_preferred(angle, best_angle)) {
+                best_angle = angle;
+            }
+        }
+    }
+
+    if (uniforms.steering == STEERING_GRADIENT) {
+        if (total_reading <= 0.0) {
+            return 0.0;
+        }
+        return agent.turn_strength * weighted_angle / total_reading;
+    }
+    return agent.turn_strength * best_angle;
+}
+
+// Must match HashGrid::new
+fn grid_dimensions() -> vec2<u32> {
+    let size = uniforms.neighbour_radius;
+    return (uniforms.dimensions + size - 1u) / size;
+}
+
+// Range of hash_sorted holding the agents in a grid cell
+fn grid_cell_agents(cell: vec2<u32>) -> vec2<u32> {
+    let grid = grid_dimensions();
+    let index = cell.y * grid.x + cell.x;
+    let start = hash_cells[grid.x * grid.y + index];
+    return vec2<u32>(start, start + hash_cells[index]);
+}
+
+// Gathers the other live agents within neighbour_radius. Grid cells are neighbour_radius wide,
+// so the 3x3 block around the agent's cell holds every candidate.
+fn neighbourhood(agent_id: u32, position: vec2<f32>) -> Neighbourhood {
+    let grid = vec2<i32>(grid_dimensions());
+    let centre = vec2<i32>(position) / i32(uniforms.neighbour_radius);
+    let radius = f32(uniforms.neighbour_radius);
+    var near = Neighbourhood(vec2<f32>(0.0), vec2<f32>(0.0), vec2<f32>(0.0), 0.0);
+    for (var j: i32 = -1; j <= 1; j++) {
+        for (var i: i32 = -1; i <= 1; i++) {
+            let cell = centre + vec2<i32>(i, j);
+            if (any(cell < vec2<i32>(0)) || any(cell >= grid)) {
+                continue;
+            }
+            let range = grid_cell_agents(vec2<u32>(cell));
+            for (var k = range.x; k < range.y; k++) {
+                let other = hash_sorted[k];
+                let neighbour = agent_src[other];
+                let offset = position - neighbour.position;
+                let distance = length(offset);
+                if (other == agent_id || distance >= radius) {
+                    continue;
+                }
+                if (distance > 0.0) {
+                    near.push += offset / distance * (1.0 - distance / radius);
+                }
+                near.heading_total += vec2<f32>(cos(neighbour.angle), sin(neighbour.angle));
+                near.position_total += neighbour.position;
+                near.count += 1.0;
+            }
+        }
+    }
+    return near;
+}
+
+// Boids-style turn towards a weighted sum of separation, alignment with the neighbours' mean
+// heading and cohesion towards their centre, each term at most unit length
+fn flock(agent: Agent, near: Neighbourhood) -> f32 {
+    if (near.count == 0.0) {
+        return 0.0;
+    }
+    let radius = f32(uniforms.neighbour_radius);
+    let desired = uniforms.separation * near.push
+        + uniforms.alignment * near.heading_total / near.count
+        + uniforms.cohesion * (near.position_total / near.count - agent.position) / radius;
+    if (all(desired == vec2<f32>(0.0))) {
+        return 0.0;
+    }
+    return agent.turn_strength * min(length(desired), 1.0) * angle_to(agent.angle, desired);
+}
+
+// Angle to turn from `heading` to face `direction`, in [-PI, PI]
+fn angle_to(heading: f32, direction: vec2<f32>) -> f32 {
+    let turn = atan2(direction.y, direction.x) - heading;
+    return turn - 2.0 * PI * round(turn / (2.0 * PI));
+}
+
+// Scales `value` by a random factor within +-mutation, keeping it in [0, max]
+fn mutate_trait(value: f32, max_value: f32, seed: vec2<f32>) -> f32 {
+    let change = uniforms.mutation * (2.0 * hash_2d(seed) - 1.0);
+    return clamp(value * (1.0 + change), 0.0, max_value);
+}
+
+// Mutates each heritable trait independently, seeded by the birth so siblings of one step differ
+fn mutate(agent: ptr<function, Agent>, seed: f32) {
+    let base = (*agent).position + vec2<f32>(seed, (*agent).angle);
+    (*agent).sensor_spread = mutate_trait((*agent).sensor_spread, MAX_SENSOR_SPREAD, base);
+    (*agent).sensor_distance =
+        mutate_trait((*agent).sensor_distance, MAX_SENSOR_DISTANCE, base + vec2<f32>(1.0, 0.0));
+    (*agent).speed = mutate_trait((*agent).speed, MAX_SPEED, base + vec2<f32>(2.0, 0.0));
+    (*agent).turn_strength =
+        mutate_trait((*agent).turn_strength, MAX_TURN_STRENGTH, base + vec2<f32>(3.0, 0.0));
+}
+
+// Ages the agent and trades movement for food, with faster agents paying more; predators only
+// gain energy in hunt_main. Without predators, e.g. under particle life, every species eats.
+// Returns false if it died, freeing its slot; above the split energy it halves its energy and
+// queues a sibling for spawn_main.
+fn live(agent: ptr<function, Agent>, agent_id: u32) -> bool {
+    (*agent).age += 1u;
+    if (uniforms.predators == 0u || (*agent).species == SPECIES_PREY) {
+        let food = env_src[cell_index(vec2<u32>((*agent).position))].food_level;
+        (*agent).energy += uniforms.food_energy * food;
+    }
+    (*agent).energy -= uniforms.move_cost * (*agent).speed;
+
+    if ((*agent).energy <= 0.0 || (uniforms.max_age > 0u && (*agent).age >= uniforms.max_age)) {
+        (*agent).alive = 0u;
+        let free = atomicAdd(&lifecycle.free_count, 1);
+        lifecycle.slots[free] = agent_id;
+        return false;
+    }
+    if ((*agent).energy >= uniforms.split_energy) {
+        (*agent).energy *= 0.5;
+        (*agent).age = 0u;
+        let birth = atomicAdd(&lifecycle.birth_count, 1u);
+        lifecycle.slots[arrayLength(&agent_src) + birth] = agent_id;
+    }
+    return true;
+}
 
 @compute
-@workgroup_size(8, 1, 1)
+@workgroup_size(64, 1, 1)
 fn compute_main(
     in: ComputeInput,
 ) {
     let agent_id = in.global_id.x;
+    if (agent_id >= arrayLength(&agent_src)) {
+        return;
+    }
+    // Free slots are carried over so both halves of the ping-pong pair agree
+    if (agent_src[agent_id].alive == 0u) {
+        agent_dest[agent_id] = agent_src[agent_id];
+        return;
+    }
     let agent_pos = agent_src[agent_id].position;
     let agent_x = agent_pos.x;
     let agent_y = agent_pos.y;
@@ -41,9 +425,9 @@ fn compute_main(
     let agent_turn_speed = agent_src[agent_id].turn_speed;
     let agent_hash = hash_2d(agent_pos + agent_angle);
 
-    let speed = 1.0;
+    let speed = agent_src[agent_id].speed;
 
-    var new_agent: Agent;
+    var new_agent = agent_src[agent_id];
     new_agent.position.x = speed * cos(agent_angle) + agent_x;
     new_agent.position.y = speed * sin(agent_angle) + agent_y;
     new_agent.angle = agent_angle + agent_turn_speed;
@@ -52,73 +436,234 @@ fn compute_main(
     // Wall hit detection
     if (new_agent.position.x < 0.0
         || new_agent.position.y < 0.0
-        || u32(new_agent.position.x) > uniforms.dimensions.x
-        || u32(new_agent.position.y) > uniforms.dimensions.y) {
+        || u32(new_agent.position.x) >= uniforms.dimensions.x
+        || u32(new_agent.position.y) >= uniforms.dimensions.y
+        || is_blocked(vec2<u32>(new_agent.position))) {
         new_agent.position = agent_pos;
         new_agent.angle = agent_hash * 6.28;
     }
 
-    // Pheromone detection
-    let detect_angle_spread = 1.0;
-    let detect_distance = 6.0;
-    let detect_influence = 0.1;
-    let angle_left = agent_angle - detect_angle_spread;
-    let angle_right = agent_angle + detect_angle_spread;
-    let origin_left = vec2<i32>(
-        i32( detect_distance * cos(angle_left) + agent_x ),
-        i32( detect_distance * sin(angle_left) + agent_y ),
-    );
-    let origin_straight = vec2<i32>(
-        i32( detect_distance * cos(agent_angle) + agent_x ),
-        i32( detect_distance * sin(agent_angle) + agent_y ),
-    );
-    let origin_right = vec2<i32>(
-        i32( detect_distance * cos(angle_right) + agent_x ),
-        i32( detect_distance * sin(angle_right) + agent_y ),
-    );
-    var pheromones_left = 0.0;
-    var pheromones_straight = 0.0;
-    var pheromones_right = 0.0;
-    for (var i: i32 = -2; i <= 2; i++) {
-        for (var j: i32 = -2; j <= 2; j++) {
-            let left_check = vec2<u32>(
-                u32(origin_left.x + i),
-                u32(origin_left.y + j),
-            );
-            let straight_check = vec2<u32>(
-                u32(origin_straight.x + i),
-                u32(origin_straight.y + j),
-            );
-            let right_check = vec2<u32>(
-                u32(origin_right.x + i),
-                u32(origin_right.y + j),
-            );
-
-            if (left_check.x >= uniforms.dimensions.x
-                || left_check.y >= uniforms.dimensions.y
-                || straight_check.x >= uniforms.dimensions.x
-                || straight_check.y >= uniforms.dimensions.y
-                || right_check.x >= uniforms.dimensions.x
-                || right_check.y >= uniforms.dimensions.y) {
+    // Pheromone detection, blended with flocking and then neighbour avoidance, both through the
+    // spatial hash
+    let pheromone_turn = steer(agent_src[agent_id]);
+    if (uniforms.avoidance > 0.0 || uniforms.flocking > 0.0) {
+        let near = neighbourhood(agent_id, agent_pos);
+        new_agent.angle += mix(pheromone_turn, flock(agent_src[agent_id], near), uniforms.flocking);
+        if (uniforms.avoidance > 0.0 && any(near.push != vec2<f32>(0.0))) {
+            let strength = uniforms.avoidance * min(length(near.push), 1.0);
+            new_agent.angle += strength * angle_to(agent_angle, near.push);
+        }
+    } else {
+        new_agent.angle += pheromone_turn;
+    }
+
+    // Nested, as naga evaluates both sides of && and live() has side effects
+    if (uniforms.lifecycle != 0u) {
+        if (!live(&new_agent, agent_id)) {
+            agent_dest[agent_id] = new_agent;
+            return;
+        }
+    }
+
+    agent_dest[agent_id] = new_agent;
+    // Organism cells are settled by settle_main, once the bond pass has moved them
+    if (agent_id >= uniforms.organism_cells) {
+        settle(agent_id, new_agent);
+    }
+}
+
+// Draws an agent where it ended the step. Unless it is a particle, it also lays its trail and
+// marks the occupancy grid for predators.
+fn settle(agent_id: u32, agent: Agent) {
+    let cell = vec2<u32>(agent.position);
+    if (uniforms.particle_life != 0u) {
+        textureStore(agent_texture, cell, species_colour(agent.species));
+        return;
+    }
+    deposit_at(agent.position, agent.species);
+    var color = vec4<f32>(1.0, 1.0, 1.0, 1.0);
+    if (agent.species == SPECIES_PREDATOR) {
+        color = vec4<f32>(1.0, 0.3, 0.2, 1.0);
+    } else if (uniforms.predators != 0u) {
+        // Any one of the prey sharing a cell can be caught
+        atomicMax(&occupancy[cell_index(cell)], agent_id + 1u);
+    }
+    textureStore(agent_texture, cell, color);
+}
+
+// Settles the organism cells after the bond pass has pulled them into shape, so their trail,
+// occupancy and markers follow the bodies rather than where the cells steered on their own
+@compute
+@workgroup_size(64, 1, 1)
+fn settle_main(
+    in: ComputeInput,
+) {
+    let agent_id = in.global_id.x;
+    if (agent_id >= uniforms.organism_cells || agent_dest[agent_id].alive == 0u) {
+        return;
+    }
+    settle(agent_id, agent_dest[agent_id]);
+}
+
+// Force between two particles `r` of their interaction radius apart: repulsion fading out at
+// PARTICLE_REPULSION_RANGE, then a peak of `attraction` halfway to the radius
+fn particle_force(r: f32, attraction: f32) -> f32 {
+    if (r < PARTICLE_REPULSION_RANGE) {
+        return r / PARTICLE_REPULSION_RANGE - 1.0;
+    }
+    let peak = abs(2.0 * r - 1.0 - PARTICLE_REPULSION_RANGE) / (1.0 - PARTICLE_REPULSION_RANGE);
+    return attraction * (1.0 - peak);
+}
+
+// Sum of the forces on a particle from the others within its radius for their species. Radii
+// are at most neighbour_radius, so like neighbourhood() the 3x3 grid cells around it suffice.
+fn particle_forces(agent_id: u32, particle: Agent) -> vec2<f32> {
+    let grid = vec2<i32>(grid_dimensions());
+    let centre = vec2<i32>(particle.position) / i32(uniforms.neighbour_radius);
+    let row = particle.species * MAX_SPECIES;
+    var force = vec2<f32>(0.0);
+    for (var j: i32 = -1; j <= 1; j++) {
+        for (var i: i32 = -1; i <= 1; i++) {
+            let cell = centre + vec2<i32>(i, j);
+            if (any(cell < vec2<i32>(0)) || any(cell >= grid)) {
                 continue;
             }
-            pheromones_left += env_src[left_check.x * uniforms.dimensions.x + left_check.y].pheromone_level;
-            pheromones_straight += env_src[straight_check.x * uniforms.dimensions.x + straight_check.y].pheromone_level;
-            pheromones_right += env_src[right_check.x * uniforms.dimensions.x + right_check.y].pheromone_level;
+            let range = grid_cell_agents(vec2<u32>(cell));
+            for (var k = range.x; k < range.y; k++) {
+                let other = hash_sorted[k];
+                let neighbour = agent_src[other];
+                let pair = interactions.pairs[row + neighbour.species];
+                let radius = min(pair.y, f32(uniforms.neighbour_radius));
+                let offset = neighbour.position - particle.position;
+                let distance = length(offset);
+                if (other == agent_id || distance <= 0.0 || distance >= radius) {
+                    continue;
+                }
+                force += offset / distance * particle_force(distance / radius, pair.x);
+            }
         }
     }
-    if (pheromones_left > pheromones_right && pheromones_left > pheromones_straight) {
-        new_agent.angle -= detect_influence * detect_angle_spread;
-    } else if (pheromones_right > pheromones_straight) {
-        new_agent.angle += detect_influence * detect_angle_spread;
+    return force;
+}
+
+// Distinct hues for the particle life species
+fn species_colour(species: u32) -> vec4<f32> {
+    let hue = f32(species) / f32(max(uniforms.species, 1u));
+    let rgb = 0.6 + 0.4 * cos(2.0 * PI * (hue + vec3<f32>(0.0, 2.0 / 3.0, 1.0 / 3.0)));
+    return vec4<f32>(rgb, 1.0);
+}
+
+// Particle life in place of compute_main: accelerate by the interaction forces, lose a fraction
+// of the velocity to friction and move by the rest, bouncing off walls. The heading follows the
+// velocity so the heading stats still apply. Particles leave no trail.
+@compute
+@workgroup_size(64, 1, 1)
+fn particle_life_main(
+    in: ComputeInput,
+) {
+    let agent_id = in.global_id.x;
+    if (agent_id >= arrayLength(&agent_src)) {
+        return;
+    }
+    var particle = agent_src[agent_id];
+    if (particle.alive == 0u) {
+        agent_dest[agent_id] = particle;
+        return;
     }
 
-    // Draw the new agent's data
-    agent_dest[agent_id] = new_agent;
-    let agent_index = u32(new_agent.position.x) * uniforms.dimensions.x + u32(new_agent.position.y);
-    env_dest[agent_index].pheromone_level = 1.0;
-    textureStore(agent_texture,
-        vec2<i32>(i32(new_agent.position.x), i32(new_agent.position.y)),
-        vec4<f32>(1.0, 1.0, 1.0, 1.0)
-    );
+    let force = particle_forces(agent_id, particle);
+    var velocity = particle.velocity * (1.0 - uniforms.friction) + uniforms.interaction_force * force;
+    let speed = length(velocity);
+    if (speed > MAX_SPEED) {
+        velocity *= MAX_SPEED / speed;
+    }
+    let position = particle.position + velocity;
+    if (position.x < 0.0
+        || position.y < 0.0
+        || u32(position.x) >= uniforms.dimensions.x
+        || u32(position.y) >= uniforms.dimensions.y
+        || is_blocked(vec2<u32>(position))) {
+        velocity = -velocity;
+    } else {
+        particle.position = position;
+    }
+    particle.velocity = velocity;
+    if (speed > 0.0) {
+        particle.angle = atan2(velocity.y, velocity.x);
+    }
+
+    // Nested, as naga evaluates both sides of && and live() has side effects
+    if (uniforms.lifecycle != 0u) {
+        if (!live(&particle, agent_id)) {
+            agent_dest[agent_id] = particle;
+            return;
+        }
+    }
+    agent_dest[agent_id] = particle;
+    if (agent_id >= uniforms.organism_cells) {
+        settle(agent_id, particle);
+    }
+}
+
+// Places the siblings queued by compute_main into free slots, heading away from their
+// parents with mutated traits. Siblings left without a free slot are dropped.
+@compute
+@workgroup_size(64, 1, 1)
+fn spawn_main(
+    in: ComputeInput,
+) {
+    let birth = in.global_id.x;
+    if (birth >= atomicLoad(&lifecycle.birth_count)) {
+        return;
+    }
+    // Only this pass pops, so a failed pop can put its decrement back without losing a slot
+    let free = atomicSub(&lifecycle.free_count, 1) - 1;
+    if (free < 0) {
+        atomicAdd(&lifecycle.free_count, 1);
+        return;
+    }
+    var sibling = agent_dest[lifecycle.slots[arrayLength(&agent_src) + birth]];
+    sibling.angle += PI;
+    mutate(&sibling, f32(birth));
+    agent_dest[lifecycle.slots[free]] = sibling;
+}
+
+// Each predator eats at most one prey marked in the occupancy grid within a cell of it. Claiming
+// a cell empties it, so no prey is eaten twice. Runs after spawn_main, so prey eaten here have
+// already placed this step's siblings.
+@compute
+@workgroup_size(64, 1, 1)
+fn hunt_main(
+    in: ComputeInput,
+) {
+    let agent_id = in.global_id.x;
+    if (agent_id >= arrayLength(&agent_dest)) {
+        return;
+    }
+    let hunter = agent_dest[agent_id];
+    if (hunter.alive == 0u || hunter.species != SPECIES_PREDATOR) {
+        return;
+    }
+    let centre = vec2<i32>(hunter.position);
+    for (var i: i32 = -1; i <= 1; i++) {
+        for (var j: i32 = -1; j <= 1; j++) {
+            let cell = centre + vec2<i32>(i, j);
+            if (cell.x < 0
+                || cell.y < 0
+                || u32(cell.x) >= uniforms.dimensions.x
+                || u32(cell.y) >= uniforms.dimensions.y) {
+                continue;
+            }
+            let marked = atomicExchange(&occupancy[cell_index(vec2<u32>(cell))], 0u);
+            if (marked == 0u) {
+                continue;
+            }
+            let prey = marked - 1u;
+            agent_dest[prey].alive = 0u;
+            let free = atomicAdd(&lifecycle.free_count, 1);
+            lifecycle.slots[free] = prey;
+            agent_dest[agent_id].energy = hunter.energy + uniforms.predator_gain;
+            return;
+        }
+    }
 }
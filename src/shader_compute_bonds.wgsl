@@ -0,0 +1,211 @@
+// Keeps the organism cells bonded after the agent pass has moved them, in passes over the
+// organism cells only:
+//   pulse_main     turns each cell towards its body's heading and thrusts it while contracting
+//   solve_main     pulls each cell towards the rest length of its springs (Jacobi iteration)
+//   apply_main     copies what either of the above staged back into the agents
+//   draw_main      draws every bond into the agent texture
+// pulse and apply run once, then solve and apply once per constraint iteration, then draw.
+// settle_main in the agent shader then deposits and draws the cells where these passes left them.
+//
+// Swimming is kinematic: pulse_main moves each cell forward by pulse_thrust while its springs
+// contract, rather than the stroke pushing against the field.
+
+struct Agent {
+    position: vec2<f32>,
+    velocity: vec2<f32>,
+    angle: f32,
+    turn_speed: f32,
+    energy: f32,
+    age: u32,
+    // Zero for a free slot
+    alive: u32,
+    sensor_spread: f32,
+    sensor_distance: f32,
+    speed: f32,
+    turn_strength: f32,
+    species: u32,
+};
+
+struct Bond {
+    // Agent slot at the other end, or NO_BOND
+    other: u32,
+    rest_length: f32,
+    // Fraction of the stretch corrected per iteration
+    stiffness: f32,
+    // Offset into the pulse cycle, from 0 to 1
+    phase: f32,
+}
+
+struct Uniforms {
+    dimensions: vec2<u32>,
+    // Rewritten every step to drive the pulse
+    step: u32,
+    // Steps per pulse, zero for none
+    pulse_period: u32,
+    // Fraction the springs shorten by at the peak of a pulse
+    pulse_amplitude: f32,
+    // Cells moved forward per step at the fastest point of a contraction
+    pulse_thrust: f32,
+}
+
+struct ComputeInput {
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+}
+
+// Must match MAX_BONDS and NO_BOND in organisms.rs
+const MAX_BONDS: u32 = 4u;
+const NO_BOND: u32 = 0xffffffffu;
+const PI: f32 = 3.14159265;
+// Fraction of the way each cell turns per step towards its bonded neighbours' mean heading
+const HEADING_ALIGNMENT: f32 = 0.5;
+const BOND_COLOUR: vec4<f32> = vec4<f32>(0.4, 0.8, 1.0, 1.0);
+// Longest bond drawn in full, in cells, in case a body is torn across the world
+const MAX_DRAWN_LENGTH: u32 = 64u;
+
+// The agent texture format, declared once for hdr.rs to switch
+alias SimTexture = texture_storage_2d<rgba8unorm, write>;
+
+@group(0) @binding(0) var<storage, read_write> agents: array<Agent>;
+// MAX_BONDS per organism cell, which are the first agent slots
+@group(0) @binding(1) var<storage, read> bonds: array<Bond>;
+// Per organism cell, the position and heading staged for apply_main
+@group(0) @binding(2) var<storage, read_write> staged: array<vec4<f32>>;
+@group(0) @binding(3) var agent_texture: SimTexture;
+@group(0) @binding(4) var obstacle_mask: texture_2d<f32>;
+
+@group(1) @binding(0) var<uniform> uniforms: Uniforms;
+
+fn cell_count() -> u32 {
+    return arrayLength(&bonds) / MAX_BONDS;
+}
+
+fn is_open(position: vec2<f32>) -> bool {
+    return position.x >= 0.0
+        && position.y >= 0.0
+        && u32(position.x) < uniforms.dimensions.x
+        && u32(position.y) < uniforms.dimensions.y
+        && textureLoad(obstacle_mask, vec2<u32>(position), 0).r <= 0.5;
+}
+
+// Where a bond is in the pulse cycle, from 0 to 1
+fn pulse_time(bond: Bond) -> f32 {
+    return fract(f32(uniforms.step) / f32(uniforms.pulse_period) + bond.phase);
+}
+
+// How far the springs have shortened, rising from 0 to 1 and back over a cycle
+fn contraction(bond: Bond) -> f32 {
+    if (uniforms.pulse_period == 0u) {
+        return 0.0;
+    }
+    return 0.5 - 0.5 * cos(2.0 * PI * pulse_time(bond));
+}
+
+// Bond `index` of a cell, if it is in use and its other end is alive
+fn live_bond(cell: u32, index: u32) -> bool {
+    let other = bonds[cell * MAX_BONDS + index].other;
+    return other != NO_BOND && agents[other].alive != 0u;
+}
+
+@compute
+@workgroup_size(64, 1, 1)
+fn pulse_main(
+    in: ComputeInput,
+) {
+    let cell = in.global_id.x;
+    if (cell >= cell_count()) {
+        return;
+    }
+    let agent = agents[cell];
+    var heading = vec2<f32>(cos(agent.angle), sin(agent.angle));
+    var thrust = 0.0;
+    for (var i = 0u; i < MAX_BONDS; i++) {
+        if (!live_bond(cell, i)) {
+            continue;
+        }
+        let bond = bonds[cell * MAX_BONDS + i];
+        let other = agents[bond.other].angle;
+        heading += vec2<f32>(cos(other), sin(other));
+        if (uniforms.pulse_period > 0u) {
+            // Only while contracting, so each pulse is a power stroke and a glide
+            thrust = max(thrust, sin(2.0 * PI * pulse_time(bond)));
+        }
+    }
+    var angle = agent.angle;
+    if (any(heading != vec2<f32>(0.0))) {
+        let turn = atan2(heading.y, heading.x) - angle;
+        angle += HEADING_ALIGNMENT * (turn - 2.0 * PI * round(turn / (2.0 * PI)));
+    }
+    let position = agent.position + uniforms.pulse_thrust * thrust * vec2<f32>(cos(angle), sin(angle));
+    staged[cell] = vec4<f32>(position, angle, 0.0);
+}
+
+@compute
+@workgroup_size(64, 1, 1)
+fn solve_main(
+    in: ComputeInput,
+) {
+    let cell = in.global_id.x;
+    if (cell >= cell_count()) {
+        return;
+    }
+    let position = agents[cell].position;
+    var correction = vec2<f32>(0.0);
+    for (var i = 0u; i < MAX_BONDS; i++) {
+        if (!live_bond(cell, i)) {
+            continue;
+        }
+        let bond = bonds[cell * MAX_BONDS + i];
+        let offset = agents[bond.other].position - position;
+        let distance = length(offset);
+        if (distance <= 0.0) {
+            continue;
+        }
+        let rest_length = bond.rest_length * (1.0 - uniforms.pulse_amplitude * contraction(bond));
+        // Each end takes half of the correction
+        correction += 0.5 * bond.stiffness * (distance - rest_length) * offset / distance;
+    }
+    staged[cell] = vec4<f32>(position + correction, agents[cell].angle, 0.0);
+}
+
+@compute
+@workgroup_size(64, 1, 1)
+fn apply_main(
+    in: ComputeInput,
+) {
+    let cell = in.global_id.x;
+    if (cell >= cell_count() || agents[cell].alive == 0u) {
+        return;
+    }
+    let update = staged[cell];
+    agents[cell].angle = update.z;
+    if (is_open(update.xy)) {
+        agents[cell].position = update.xy;
+    }
+}
+
+// Each bond is drawn once, from its lower slot, as a dotted line a cell apart
+@compute
+@workgroup_size(64, 1, 1)
+fn draw_main(
+    in: ComputeInput,
+) {
+    let cell = in.global_id.x;
+    if (cell >= cell_count() || agents[cell].alive == 0u) {
+        return;
+    }
+    let start = agents[cell].position;
+    for (var i = 0u; i < MAX_BONDS; i++) {
+        let bond = bonds[cell * MAX_BONDS + i];
+        if (!live_bond(cell, i) || bond.other < cell) {
+            continue;
+        }
+        let end = agents[bond.other].position;
+        let steps = min(u32(ceil(distance(start, end))), MAX_DRAWN_LENGTH);
+        for (var s = 0u; s <= steps; s++) {
+            let point = mix(start, end, f32(s) / f32(max(steps, 1u)));
+            if (is_open(point)) {
+                textureStore(agent_texture, vec2<u32>(point), BOND_COLOUR);
+            }
+        }
+    }
+}
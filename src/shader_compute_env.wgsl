@@ -1,62 +1,319 @@
 struct EnvCell {
     pheromone_level: f32,
+    food_level: f32,
+    scent_level: f32,
 }
 
 struct Uniforms {
     dimensions: vec2<u32>,
+    food_decay: f32,
+    kernel: u32,
+    sigma: f32,
+    diffusion_rate: f32,
 }
 
+// Must match DEPOSIT_SCALE in the agent shader
+const DEPOSIT_SCALE: f32 = 4096.0;
+
+// Must match DiffusionKernel::shader_id
+const KERNEL_BOX3: u32 = 0u;
+const KERNEL_BOX5: u32 = 1u;
+const KERNEL_GAUSSIAN: u32 = 2u;
+const KERNEL_LAPLACIAN: u32 = 3u;
+// Gaussian taps beyond this many cells either side are dropped
+const MAX_GAUSSIAN_RADIUS: i32 = 16;
+
+// The tiled entry point loads an 8x8 block plus a halo wide enough for the box5 kernel
+const TILE_SIZE: i32 = 8;
+const TILE_HALO: i32 = 2;
+const TILE_SPAN: i32 = 12;
+
 struct ComputeInput {
     @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
 }
 
+// Switched to rgba16float by hdr.rs along with the agent texture
+alias SimTexture = texture_storage_2d<rgba8unorm, write>;
+
 @group(0) @binding(0) var<storage, read> env_src: array<EnvCell>;
 @group(0) @binding(1) var<storage, read_write> env_dest: array<EnvCell>;
-@group(0) @binding(2) var env_texture: texture_storage_2d<rgba8unorm, write>;
+@group(0) @binding(2) var env_texture: SimTexture;
+@group(0) @binding(3) var obstacle_mask: texture_2d<f32>;
+@group(0) @binding(4) var food_emission: texture_2d<f32>;
+// The prey trail for every cell, then the predator scent for every cell
+@group(0) @binding(5) var<storage, read> deposits: array<u32>;
+// Rows blurred by the first Gaussian pass, as (pheromone, food, scent, unused)
+@group(0) @binding(6) var<storage, read_write> blur_scratch: array<vec4<f32>>;
 
 @group(1) @binding(0) var<uniform> uniforms: Uniforms;
 
+// (pheromone, food, scent, 1 if open else 0) of the block and its halo, for the tiled entry point
+var<workgroup> tile: array<vec4<f32>, 144>;
+
+fn cell_index(cell: vec2<u32>) -> u32 {
+    return cell.y * uniforms.dimensions.x + cell.x;
+}
+
+fn is_blocked(cell: vec2<u32>) -> bool {
+    return textureLoad(obstacle_mask, cell, 0).r > 0.5;
+}
+
+// Whether a cell is inside the world and open, so it takes part in diffusion
+fn is_open(cell: vec2<i32>) -> bool {
+    let check = vec2<u32>(cell);
+    return cell.x >= 0
+        && cell.y >= 0
+        && check.x < uniforms.dimensions.x
+        && check.y < uniforms.dimensions.y
+        && !is_blocked(check);
+}
+
+// (pheromone, food, scent) at a cell, including this step's agent deposits
+fn levels_at(cell: vec2<i32>) -> vec3<f32> {
+    let index = cell_index(vec2<u32>(cell));
+    let scent_index = index + uniforms.dimensions.x * uniforms.dimensions.y;
+    let env = env_src[index];
+    return vec3<f32>(
+        env.pheromone_level + f32(deposits[index]) / DEPOSIT_SCALE,
+        env.food_level,
+        env.scent_level + f32(deposits[scent_index]) / DEPOSIT_SCALE,
+    );
+}
+
+// Mean of the open cells in a square of the given radius
+fn box_blur(cell: vec2<i32>, radius: i32) -> vec3<f32> {
+    var total = vec3<f32>(0.0);
+    var cells = 0.0;
+    for (var i: i32 = -radius; i <= radius; i++) {
+        for (var j: i32 = -radius; j <= radius; j++) {
+            let check = cell + vec2<i32>(i, j);
+            if (!is_open(check)) {
+                continue;
+            }
+            total += levels_at(check);
+            cells += 1.0;
+        }
+    }
+    return total / cells;
+}
+
+fn gaussian_radius() -> i32 {
+    return min(i32(ceil(3.0 * uniforms.sigma)), MAX_GAUSSIAN_RADIUS);
+}
+
+fn gaussian_weight(offset: i32) -> f32 {
+    let x = f32(offset);
+    return exp(-x * x / (2.0 * uniforms.sigma * uniforms.sigma));
+}
+
+// Discrete 4-neighbour Laplacian; walls and the world edge do not exchange anything
+fn laplacian(cell: vec2<i32>, centre: vec3<f32>) -> vec3<f32> {
+    var offsets = array<vec2<i32>, 4>(
+        vec2<i32>(1, 0),
+        vec2<i32>(-1, 0),
+        vec2<i32>(0, 1),
+        vec2<i32>(0, -1),
+    );
+    var total = vec3<f32>(0.0);
+    for (var i = 0; i < 4; i++) {
+        let check = cell + offsets[i];
+        if (is_open(check)) {
+            total += levels_at(check) - centre;
+        }
+    }
+    return total;
+}
+
+// Fills `tile` with the workgroup's block and halo, each invocation loading a few cells
+fn load_tile(in: ComputeInput) {
+    let origin = vec2<i32>(in.workgroup_id.xy) * TILE_SIZE - TILE_HALO;
+    for (var i = i32(in.local_index); i < TILE_SPAN * TILE_SPAN; i += TILE_SIZE * TILE_SIZE) {
+        let cell = origin + vec2<i32>(i % TILE_SPAN, i / TILE_SPAN);
+        var value = vec4<f32>(0.0);
+        if (is_open(cell)) {
+            value = vec4<f32>(levels_at(cell), 1.0);
+        }
+        tile[i] = value;
+    }
+    workgroupBarrier();
+}
+
+// Tile entry of a cell given relative to the invocation's own cell
+fn tile_at(local: vec2<i32>, offset: vec2<i32>) -> vec4<f32> {
+    let at = local + offset + TILE_HALO;
+    return tile[at.y * TILE_SPAN + at.x];
+}
+
+fn tiled_box_blur(local: vec2<i32>, radius: i32) -> vec3<f32> {
+    var total = vec4<f32>(0.0);
+    for (var i: i32 = -radius; i <= radius; i++) {
+        for (var j: i32 = -radius; j <= radius; j++) {
+            total += tile_at(local, vec2<i32>(i, j));
+        }
+    }
+    return total.xyz / total.w;
+}
+
+fn tiled_laplacian(local: vec2<i32>, centre: vec3<f32>) -> vec3<f32> {
+    var offsets = array<vec2<i32>, 4>(
+        vec2<i32>(1, 0),
+        vec2<i32>(-1, 0),
+        vec2<i32>(0, 1),
+        vec2<i32>(0, -1),
+    );
+    var total = vec3<f32>(0.0);
+    for (var i = 0; i < 4; i++) {
+        let neighbor = tile_at(local, offsets[i]);
+        total += neighbor.w * (neighbor.xyz - centre);
+    }
+    return total;
+}
+
+// First pass of the separable Gaussian: blur along rows into blur_scratch
 @compute
 @workgroup_size(8, 8, 1)
-fn compute_main(
+fn blur_rows_main(
     in: ComputeInput,
 ) {
-    let cell_x = in.global_id.x;
-    let cell_y = in.global_id.y;
-    let cell_ind = cell_x * uniforms.dimensions.x + cell_y;
-    let prev_cell = env_src[cell_ind];
-
-
-    let prev_pheromone = prev_cell.pheromone_level;
-    var new_pheromone = prev_pheromone;
-
-    var neighborhood_total = 0.0;
-    var neighborhood_cells = 0;
-    for (var i: i32 = -2; i <= 2; i++) {
-        for (var j: i32 = -2; j <= 2; j++) {
-            let check_x = u32( i32( cell_x ) + i );
-            let check_y = u32( i32( cell_y ) + j );
-            if (check_x < 0
-            || check_x >= uniforms.dimensions.x
-            || check_y < 0
-            || check_y >= uniforms.dimensions.y) {
-                continue;
-            }
+    let cell = vec2<i32>(in.global_id.xy);
+    if (!is_open(cell)) {
+        return;
+    }
+    let radius = gaussian_radius();
+    var total = vec3<f32>(0.0);
+    var weights = 0.0;
+    for (var i: i32 = -radius; i <= radius; i++) {
+        let check = cell + vec2<i32>(i, 0);
+        if (!is_open(check)) {
+            continue;
+        }
+        let weight = gaussian_weight(i);
+        total += weight * levels_at(check);
+        weights += weight;
+    }
+    blur_scratch[cell_index(in.global_id.xy)] = vec4<f32>(total / weights, 0.0);
+}
 
-            neighborhood_total += env_src[check_x * uniforms.dimensions.x + check_y].pheromone_level;
-            neighborhood_cells += 1;
+// Second pass of the separable Gaussian: blur the row-blurred field along columns
+fn blur_columns(cell: vec2<i32>) -> vec3<f32> {
+    let radius = gaussian_radius();
+    var total = vec3<f32>(0.0);
+    var weights = 0.0;
+    for (var j: i32 = -radius; j <= radius; j++) {
+        let check = cell + vec2<i32>(0, j);
+        if (!is_open(check)) {
+            continue;
         }
+        let weight = gaussian_weight(j);
+        total += weight * blur_scratch[cell_index(vec2<u32>(check))].xyz;
+        weights += weight;
     }
-    let neighborhood_blend = neighborhood_total / f32(neighborhood_cells);
-    new_pheromone = (0.1 * neighborhood_blend) + (0.9 * new_pheromone);
-    new_pheromone = max(0.0, new_pheromone - 0.005);
+    return total / weights;
+}
+
+// Writes a wall cell to the field and the texture
+fn store_wall(cell: vec2<u32>) {
+    var wall_cell: EnvCell;
+    wall_cell.pheromone_level = 0.0;
+    wall_cell.food_level = 0.0;
+    wall_cell.scent_level = 0.0;
+    env_dest[cell_index(cell)] = wall_cell;
+    textureStore(env_texture,
+        cell,
+        vec4<f32>(0.2, 0.2, 0.25, 1.0)
+    );
+}
+
+// Decays the diffused (pheromone, food, scent), adds food emission and writes the cell
+fn store_cell(cell: vec2<u32>, diffused: vec3<f32>) {
+    let new_pheromone = max(0.0, diffused.x - 0.005);
+    // Scent fades like the trail
+    let new_scent = max(0.0, diffused.z - 0.005);
+
+    // Food decays at its own rate and is topped up by the sources
+    var new_food = max(0.0, diffused.y - uniforms.food_decay);
+    new_food += textureLoad(food_emission, cell, 0).r;
 
     var new_cell: EnvCell;
     new_cell.pheromone_level = new_pheromone;
+    new_cell.food_level = new_food;
+    new_cell.scent_level = new_scent;
 
-    env_dest[cell_ind] = new_cell;
+    env_dest[cell_index(cell)] = new_cell;
     textureStore(env_texture,
-        vec2<u32>(cell_x, cell_y),
-        vec4<f32>(new_pheromone, new_pheromone, new_pheromone, 1.0)
+        cell,
+        vec4<f32>(max(new_pheromone, new_scent), max(new_pheromone, new_food), new_pheromone, 1.0)
     );
 }
+
+// Reads every neighbour from env_src directly
+@compute
+@workgroup_size(8, 8, 1)
+fn compute_main(
+    in: ComputeInput,
+) {
+    // Walls hold no pheromone
+    if (is_blocked(in.global_id.xy)) {
+        store_wall(in.global_id.xy);
+        return;
+    }
+
+    let cell = vec2<i32>(in.global_id.xy);
+    let prev = levels_at(cell);
+
+    // Blur kernels are blended into the cell by the diffusion rate, the Laplacian is scaled by it
+    var diffused: vec3<f32>;
+    switch uniforms.kernel {
+        case KERNEL_BOX3: {
+            diffused = mix(prev, box_blur(cell, 1), uniforms.diffusion_rate);
+        }
+        case KERNEL_GAUSSIAN: {
+            diffused = mix(prev, blur_columns(cell), uniforms.diffusion_rate);
+        }
+        case KERNEL_LAPLACIAN: {
+            diffused = prev + uniforms.diffusion_rate * laplacian(cell, prev);
+        }
+        case KERNEL_BOX5, default: {
+            diffused = mix(prev, box_blur(cell, 2), uniforms.diffusion_rate);
+        }
+    }
+    store_cell(in.global_id.xy, diffused);
+}
+
+// Same result as compute_main, but the block and halo are loaded into workgroup memory once
+// instead of every invocation reading all of its neighbours. The Gaussian column pass still
+// reads blur_scratch directly, since its radius can exceed the halo.
+@compute
+@workgroup_size(8, 8, 1)
+fn compute_tiled_main(
+    in: ComputeInput,
+) {
+    load_tile(in);
+
+    if (is_blocked(in.global_id.xy)) {
+        store_wall(in.global_id.xy);
+        return;
+    }
+
+    let local = vec2<i32>(i32(in.local_index) % TILE_SIZE, i32(in.local_index) / TILE_SIZE);
+    let prev = tile_at(local, vec2<i32>(0, 0)).xyz;
+
+    var diffused: vec3<f32>;
+    switch uniforms.kernel {
+        case KERNEL_BOX3: {
+            diffused = mix(prev, tiled_box_blur(local, 1), uniforms.diffusion_rate);
+        }
+        case KERNEL_GAUSSIAN: {
+            diffused = mix(prev, blur_columns(vec2<i32>(in.global_id.xy)), uniforms.diffusion_rate);
+        }
+        case KERNEL_LAPLACIAN: {
+            diffused = prev + uniforms.diffusion_rate * tiled_laplacian(local, prev);
+        }
+        case KERNEL_BOX5, default: {
+            diffused = mix(prev, tiled_box_blur(local, 2), uniforms.diffusion_rate);
+        }
+    }
+    store_cell(in.global_id.xy, diffused);
+}
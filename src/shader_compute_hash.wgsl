@@ -0,0 +1,176 @@
+// Counting sort of the live agents into a uniform grid, rebuilt every step:
+//   count_main                 counts the agents in each cell and ranks them within it
+//   scan_blocks_main           exclusive scan of the counts within blocks of SCAN_BLOCK cells
+//   scan_block_sums_main       exclusive scan of the block totals, in a single workgroup
+//   add_block_offsets_main     adds each block's offset to its cells' starts
+//   scatter_main               writes every agent's index to its cell's range of `sorted`
+// Agents in grid cell c are then sorted[start(c) .. start(c) + count(c)].
+
+struct Agent {
+    position: vec2<f32>,
+    velocity: vec2<f32>,
+    angle: f32,
+    turn_speed: f32,
+    energy: f32,
+    age: u32,
+    // Zero for a free slot
+    alive: u32,
+    sensor_spread: f32,
+    sensor_distance: f32,
+    speed: f32,
+    turn_strength: f32,
+    species: u32,
+};
+
+struct Uniforms {
+    dimensions: vec2<u32>,
+    // Side of a grid cell, in world cells
+    cell_size: u32,
+}
+
+struct ComputeInput {
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+}
+
+// Each scan workgroup covers SCAN_BLOCK cells, SCAN_ITEMS per invocation. SCAN_BLOCK must match
+// spatial_hash.rs.
+const SCAN_THREADS: u32 = 256u;
+const SCAN_ITEMS: u32 = 4u;
+const SCAN_BLOCK: u32 = 1024u;
+
+@group(0) @binding(0) var<storage, read> agents: array<Agent>;
+// The agent count of every grid cell, then the index in `sorted` each cell's agents start at
+@group(0) @binding(1) var<storage, read_write> cells: array<atomic<u32>>;
+// Total count of each block, scanned in place into the block's offset
+@group(0) @binding(2) var<storage, read_write> block_sums: array<u32>;
+// Each agent's place among the agents counted into its cell
+@group(0) @binding(3) var<storage, read_write> ranks: array<u32>;
+@group(0) @binding(4) var<storage, read_write> sorted: array<u32>;
+
+@group(1) @binding(0) var<uniform> uniforms: Uniforms;
+
+var<workgroup> scan: array<u32, 256>;
+
+// Must match HashGrid::new
+fn grid_dimensions() -> vec2<u32> {
+    return (uniforms.dimensions + uniforms.cell_size - 1u) / uniforms.cell_size;
+}
+
+fn cell_count() -> u32 {
+    let grid = grid_dimensions();
+    return grid.x * grid.y;
+}
+
+fn grid_cell(position: vec2<f32>) -> u32 {
+    let grid = grid_dimensions();
+    let cell = min(vec2<u32>(max(position, vec2<f32>(0.0))) / uniforms.cell_size, grid - 1u);
+    return cell.y * grid.x + cell.x;
+}
+
+// Inclusive scan of one value per invocation across the workgroup (Hillis-Steele). The total is
+// left in scan[SCAN_THREADS - 1].
+fn scan_workgroup(local_index: u32, value: u32) -> u32 {
+    scan[local_index] = value;
+    for (var offset = 1u; offset < SCAN_THREADS; offset <<= 1u) {
+        workgroupBarrier();
+        var add = 0u;
+        if (local_index >= offset) {
+            add = scan[local_index - offset];
+        }
+        workgroupBarrier();
+        scan[local_index] += add;
+    }
+    workgroupBarrier();
+    return scan[local_index];
+}
+
+@compute
+@workgroup_size(64, 1, 1)
+fn count_main(
+    in: ComputeInput,
+) {
+    let agent_id = in.global_id.x;
+    if (agent_id >= arrayLength(&agents) || agents[agent_id].alive == 0u) {
+        return;
+    }
+    ranks[agent_id] = atomicAdd(&cells[grid_cell(agents[agent_id].position)], 1u);
+}
+
+@compute
+@workgroup_size(256, 1, 1)
+fn scan_blocks_main(
+    in: ComputeInput,
+) {
+    let count = cell_count();
+    let first = in.workgroup_id.x * SCAN_BLOCK + in.local_index * SCAN_ITEMS;
+    var before = array<u32, 4>();
+    var total = 0u;
+    for (var i = 0u; i < SCAN_ITEMS; i++) {
+        before[i] = total;
+        if (first + i < count) {
+            total += atomicLoad(&cells[first + i]);
+        }
+    }
+    let inclusive = scan_workgroup(in.local_index, total);
+    for (var i = 0u; i < SCAN_ITEMS; i++) {
+        if (first + i < count) {
+            atomicStore(&cells[count + first + i], inclusive - total + before[i]);
+        }
+    }
+    if (in.local_index == SCAN_THREADS - 1u) {
+        block_sums[in.workgroup_id.x] = inclusive;
+    }
+}
+
+@compute
+@workgroup_size(256, 1, 1)
+fn scan_block_sums_main(
+    in: ComputeInput,
+) {
+    let blocks = (cell_count() + SCAN_BLOCK - 1u) / SCAN_BLOCK;
+    var carry = 0u;
+    for (var first = 0u; first < blocks; first += SCAN_THREADS) {
+        let block = first + in.local_index;
+        var value = 0u;
+        if (block < blocks) {
+            value = block_sums[block];
+        }
+        let inclusive = scan_workgroup(in.local_index, value);
+        if (block < blocks) {
+            block_sums[block] = carry + inclusive - value;
+        }
+        carry += scan[SCAN_THREADS - 1u];
+        // Everyone has read the total before the next chunk overwrites it
+        workgroupBarrier();
+    }
+}
+
+@compute
+@workgroup_size(256, 1, 1)
+fn add_block_offsets_main(
+    in: ComputeInput,
+) {
+    let count = cell_count();
+    let offset = block_sums[in.workgroup_id.x];
+    let first = in.workgroup_id.x * SCAN_BLOCK + in.local_index * SCAN_ITEMS;
+    for (var i = 0u; i < SCAN_ITEMS; i++) {
+        if (first + i < count) {
+            atomicAdd(&cells[count + first + i], offset);
+        }
+    }
+}
+
+@compute
+@workgroup_size(64, 1, 1)
+fn scatter_main(
+    in: ComputeInput,
+) {
+    let agent_id = in.global_id.x;
+    if (agent_id >= arrayLength(&agents) || agents[agent_id].alive == 0u) {
+        return;
+    }
+    let start = atomicLoad(&cells[cell_count() + grid_cell(agents[agent_id].position)]);
+    sorted[start + ranks[agent_id]] = agent_id;
+}
@@ -0,0 +1,171 @@
+struct Agent {
+    position: vec2<f32>,
+    velocity: vec2<f32>,
+    angle: f32,
+    turn_speed: f32,
+    energy: f32,
+    age: u32,
+    // Zero for a free slot
+    alive: u32,
+    sensor_spread: f32,
+    sensor_distance: f32,
+    speed: f32,
+    turn_strength: f32,
+    species: u32,
+};
+
+struct EnvCell {
+    pheromone_level: f32,
+    food_level: f32,
+    scent_level: f32,
+}
+
+// Sums are 64-bit fixed point (value * FIXED_SCALE) split into two words, because a single
+// u32 overflows for large worlds
+struct Stats {
+    pheromone_total_lo: atomic<u32>,
+    pheromone_total_hi: atomic<u32>,
+    // Bits of a non-negative f32, which order the same way as the float
+    pheromone_max: atomic<u32>,
+    occupied_cells: atomic<u32>,
+    turn_speed_total_lo: atomic<u32>,
+    turn_speed_total_hi: atomic<u32>,
+    // Live agents, which the turn speed total is averaged over
+    population: atomic<u32>,
+    // Live agents that are predators
+    predators: atomic<u32>,
+    level_histogram: array<atomic<u32>, 16>,
+    heading_histogram: array<atomic<u32>, 16>,
+    // HISTOGRAM_BINS bins for each of sensor spread, sensor distance, speed and turn strength
+    trait_histogram: array<atomic<u32>, 64>,
+}
+
+struct ComputeInput {
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+}
+
+const FIXED_SCALE: f32 = 4096.0;
+const HISTOGRAM_BINS: u32 = 16u;
+// Pheromone levels at or above this land in the last histogram bin
+const HISTOGRAM_MAX_LEVEL: f32 = 1.0;
+const TAU: f32 = 6.2831853;
+// Must match SPECIES_PREDATOR in agents.rs
+const SPECIES_PREDATOR: u32 = 1u;
+// Upper ends of the trait histograms, must match TRAIT_RANGES in stats.rs
+const TRAIT_MAX = array<f32, 4>(3.14159265, 32.0, 4.0, 1.0);
+
+@group(0) @binding(0) var<storage, read> env_cells: array<EnvCell>;
+@group(0) @binding(1) var<storage, read> agents: array<Agent>;
+@group(0) @binding(2) var agent_texture: texture_2d<f32>;
+@group(0) @binding(3) var<storage, read_write> stats: Stats;
+
+var<workgroup> partial_sum: array<f32, 64>;
+var<workgroup> partial_max: array<f32, 64>;
+var<workgroup> local_histogram: array<atomic<u32>, 16>;
+var<workgroup> local_count: atomic<u32>;
+var<workgroup> local_traits: array<atomic<u32>, 64>;
+var<workgroup> local_predators: atomic<u32>;
+
+// Whether adding `value` to `old` wrapped around, so the high word needs a carry
+fn carries(old: u32, value: u32) -> bool {
+    return old > 0xffffffffu - value;
+}
+
+// Adds `value` to the histogram of trait `index` over [0, TRAIT_MAX), with larger values in
+// the last bin
+fn count_trait(index: u32, value: f32) {
+    var trait_max = TRAIT_MAX;
+    let scaled = max(value, 0.0) / trait_max[index] * f32(HISTOGRAM_BINS);
+    let bin = min(u32(scaled), HISTOGRAM_BINS - 1u);
+    atomicAdd(&local_traits[index * HISTOGRAM_BINS + bin], 1u);
+}
+
+// Tree reduction of partial_sum and partial_max; the results end up in element 0
+fn reduce_workgroup(local_index: u32) {
+    for (var stride = 32u; stride > 0u; stride >>= 1u) {
+        workgroupBarrier();
+        if (local_index < stride) {
+            partial_sum[local_index] += partial_sum[local_index + stride];
+            partial_max[local_index] = max(partial_max[local_index], partial_max[local_index + stride]);
+        }
+    }
+    workgroupBarrier();
+}
+
+@compute
+@workgroup_size(8, 8, 1)
+fn env_stats_main(
+    in: ComputeInput,
+) {
+    let dimensions = textureDimensions(agent_texture);
+    let cell = in.global_id.xy;
+    let in_world = cell.x < dimensions.x && cell.y < dimensions.y;
+
+    var level = 0.0;
+    if (in_world) {
+        level = env_cells[cell.y * dimensions.x + cell.x].pheromone_level;
+        let bin = min(u32(level / HISTOGRAM_MAX_LEVEL * f32(HISTOGRAM_BINS)), HISTOGRAM_BINS - 1u);
+        atomicAdd(&local_histogram[bin], 1u);
+        if (textureLoad(agent_texture, cell, 0).a > 0.0) {
+            atomicAdd(&local_count, 1u);
+        }
+    }
+    partial_sum[in.local_index] = level;
+    partial_max[in.local_index] = level;
+    reduce_workgroup(in.local_index);
+
+    if (in.local_index == 0u) {
+        let total = u32(partial_sum[0] * FIXED_SCALE);
+        if (carries(atomicAdd(&stats.pheromone_total_lo, total), total)) {
+            atomicAdd(&stats.pheromone_total_hi, 1u);
+        }
+        atomicMax(&stats.pheromone_max, bitcast<u32>(partial_max[0]));
+        atomicAdd(&stats.occupied_cells, atomicLoad(&local_count));
+    }
+    if (in.local_index < HISTOGRAM_BINS) {
+        atomicAdd(&stats.level_histogram[in.local_index], atomicLoad(&local_histogram[in.local_index]));
+    }
+}
+
+@compute
+@workgroup_size(64, 1, 1)
+fn agent_stats_main(
+    in: ComputeInput,
+) {
+    let agent_id = in.global_id.x;
+
+    var turn_speed = 0.0;
+    if (agent_id < arrayLength(&agents) && agents[agent_id].alive != 0u) {
+        let agent = agents[agent_id];
+        turn_speed = abs(agent.turn_speed);
+        let heading = fract(agent.angle / TAU);
+        let bin = min(u32(heading * f32(HISTOGRAM_BINS)), HISTOGRAM_BINS - 1u);
+        atomicAdd(&local_histogram[bin], 1u);
+        atomicAdd(&local_count, 1u);
+        if (agent.species == SPECIES_PREDATOR) {
+            atomicAdd(&local_predators, 1u);
+        }
+        count_trait(0u, agent.sensor_spread);
+        count_trait(1u, agent.sensor_distance);
+        count_trait(2u, agent.speed);
+        count_trait(3u, agent.turn_strength);
+    }
+    partial_sum[in.local_index] = turn_speed;
+    partial_max[in.local_index] = turn_speed;
+    reduce_workgroup(in.local_index);
+
+    if (in.local_index == 0u) {
+        let total = u32(partial_sum[0] * FIXED_SCALE);
+        if (carries(atomicAdd(&stats.turn_speed_total_lo, total), total)) {
+            atomicAdd(&stats.turn_speed_total_hi, 1u);
+        }
+        atomicAdd(&stats.population, atomicLoad(&local_count));
+        atomicAdd(&stats.predators, atomicLoad(&local_predators));
+    }
+    if (in.local_index < HISTOGRAM_BINS) {
+        atomicAdd(&stats.heading_histogram[in.local_index], atomicLoad(&local_histogram[in.local_index]));
+    }
+    // The workgroup has exactly as many invocations as trait bins
+    atomicAdd(&stats.trait_histogram[in.local_index], atomicLoad(&local_traits[in.local_index]));
+}
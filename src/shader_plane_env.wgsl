@@ -23,11 +23,26 @@ fn vs_main(
 var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
+@group(0) @binding(2)
+var<uniform> params: RenderParams;
+
+struct RenderParams {
+    exposure: f32,
+    tone_map: u32,
+}
+
+// Exponential tone mapping of unbounded float levels into [0, 1)
+fn tone_map(color: vec4<f32>) -> vec4<f32> {
+    if (params.tone_map == 0u) {
+        return color;
+    }
+    return vec4<f32>(1.0 - exp(-params.exposure * color.rgb), color.a);
+}
 
 @fragment
 fn fs_main(
     in: VertexOutput,
 ) -> @location(0) vec4<f32> {
     // return vec4<f32>(0.3, 0.2, 0.1, 1.0);
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    return tone_map(textureSample(t_diffuse, s_diffuse, in.tex_coords));
 }
@@ -0,0 +1,147 @@
+//! Uniform grid the live agents are counting-sorted into every step, so agent kernels can find
+//! their neighbours by scanning a few grid cells instead of every agent
+
+/// Cells scanned by one workgroup of the hash shader, must match `SCAN_BLOCK` there
+const SCAN_BLOCK: u32 = 1024;
+
+/// Grid of `cell_size` x `cell_size` world cells covering the world
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HashGrid {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl HashGrid {
+    /// Must match `grid_dimensions` in the hash and agent shaders
+    pub fn new(world_width: u32, world_height: u32, cell_size: u32) -> Self {
+        Self {
+            width: world_width.div_ceil(cell_size),
+            height: world_height.div_ceil(cell_size),
+        }
+    }
+
+    pub fn cell_count(self) -> u32 {
+        self.width * self.height
+    }
+
+    /// Workgroups of the block scan passes
+    pub fn block_count(self) -> u32 {
+        self.cell_count().div_ceil(SCAN_BLOCK)
+    }
+
+    /// The number of agents in each grid cell, then where each cell's agents start in the
+    /// sorted buffer. The counts are cleared every step.
+    pub fn cells_buf_desc(self) -> wgpu::BufferDescriptor<'static> {
+        wgpu::BufferDescriptor {
+            label: Some("Hash Cell Buffer"),
+            size: 2 * self.cell_count() as u64 * std::mem::size_of::<u32>() as u64,
+            mapped_at_creation: false,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        }
+    }
+
+    /// Byte range of the counts in the cell buffer
+    pub fn counts_size(self) -> u64 {
+        self.cell_count() as u64 * std::mem::size_of::<u32>() as u64
+    }
+
+    /// Agents counted into each block of `SCAN_BLOCK` cells, scanned into the block's offset
+    pub fn block_sums_buf_desc(self) -> wgpu::BufferDescriptor<'static> {
+        wgpu::BufferDescriptor {
+            label: Some("Hash Block Sums Buffer"),
+            size: self.block_count() as u64 * std::mem::size_of::<u32>() as u64,
+            mapped_at_creation: false,
+            usage: wgpu::BufferUsages::STORAGE,
+        }
+    }
+
+    /// Per agent slot, its place among the agents counted into the same cell
+    pub fn ranks_buf_desc(agent_capacity: u32) -> wgpu::BufferDescriptor<'static> {
+        wgpu::BufferDescriptor {
+            label: Some("Hash Rank Buffer"),
+            size: agent_capacity as u64 * std::mem::size_of::<u32>() as u64,
+            mapped_at_creation: false,
+            usage: wgpu::BufferUsages::STORAGE,
+        }
+    }
+
+    /// Indices of the live agents, ordered by grid cell
+    pub fn sorted_buf_desc(agent_capacity: u32) -> wgpu::BufferDescriptor<'static> {
+        wgpu::BufferDescriptor {
+            label: Some("Hash Sorted Agent Buffer"),
+            size: agent_capacity as u64 * std::mem::size_of::<u32>() as u64,
+            mapped_at_creation: false,
+            usage: wgpu::BufferUsages::STORAGE,
+        }
+    }
+
+    pub fn compute_shader_desc() -> wgpu::ShaderModuleDescriptor<'static> {
+        wgpu::ShaderModuleDescriptor {
+            label: Some("Hash Compute Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shader_compute_hash.wgsl").into()),
+        }
+    }
+
+    pub fn bind_layout_desc() -> wgpu::BindGroupLayoutDescriptor<'static> {
+        wgpu::BindGroupLayoutDescriptor {
+            label: Some("Hash Compute Bind Group Layout"),
+            entries: &[
+                // Agent Buffer: the agents to sort, as read by this step's agent pass
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Hash Cell Buffer: counted into, then scanned into start offsets
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Hash Block Sums Buffer: carries the scan between blocks
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Hash Rank Buffer: written by the count pass, read by the scatter pass
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Hash Sorted Agent Buffer: the scatter pass writes the sorted indices here
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        }
+    }
+}
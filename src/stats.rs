@@ -0,0 +1,332 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::config::Config;
+use crate::layout::shader_struct;
+
+pub const HISTOGRAM_BINS: usize = 16;
+/// Heritable agent traits with their CSV name and histogram range [0, max), must match
+/// `TRAIT_MAX` in the stats shader and the `MAX_*` trait limits in the agent shader
+pub const TRAIT_RANGES: [(&str, f32); 4] = [
+    ("spread", std::f32::consts::PI),
+    ("distance", 32.0),
+    ("speed", 4.0),
+    ("turn", 1.0),
+];
+/// Indices of the traits into `TRAIT_RANGES`
+pub const TRAIT_SPREAD: usize = 0;
+pub const TRAIT_DISTANCE: usize = 1;
+pub const TRAIT_SPEED: usize = 2;
+pub const TRAIT_TURN: usize = 3;
+const TRAIT_BINS: usize = TRAIT_RANGES.len() * HISTOGRAM_BINS;
+/// Rows written to the CSV between flushes, so it can be followed while the simulation runs
+/// without a write per frame
+const CSV_FLUSH_ROWS: u32 = 60;
+/// Fixed-point scale of the summed statistics, must match `FIXED_SCALE` in the stats shader
+const FIXED_SCALE: f64 = 4096.0;
+
+shader_struct! {
+    /// Per-step statistics accumulated on the GPU by the stats shader
+    #[repr(C)]
+    #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+    pub struct GpuStats {
+        pheromone_total_lo: u32,
+        pheromone_total_hi: u32,
+        pheromone_max: u32,
+        occupied_cells: u32,
+        turn_speed_total_lo: u32,
+        turn_speed_total_hi: u32,
+        population: u32,
+        predators: u32,
+        level_histogram: [u32; HISTOGRAM_BINS],
+        heading_histogram: [u32; HISTOGRAM_BINS],
+        trait_histogram: [u32; TRAIT_BINS],
+    }
+}
+
+/// Statistics of one simulation step, decoded from `GpuStats`
+#[derive(Clone, Debug)]
+pub struct FrameStats {
+    pub frame: u64,
+    pub pheromone_total: f64,
+    pub pheromone_mean: f64,
+    pub pheromone_max: f32,
+    /// Histogram of pheromone levels over [0, 1), with higher levels in the last bin
+    pub level_histogram: [u32; HISTOGRAM_BINS],
+    /// Mean magnitude of the agents' turn speed
+    pub mean_turn_speed: f64,
+    /// Number of live agents
+    pub population: u32,
+    /// Number of live agents that are predators, the rest being prey
+    pub predators: u32,
+    /// Histogram of agent headings over [0, 2pi)
+    pub heading_histogram: [u32; HISTOGRAM_BINS],
+    /// Length of the mean agent heading, from 0 when headings cancel out to 1 when all agents
+    /// move the same way. Taken from the heading histogram, so it tops out just below 1.
+    pub polarisation: f64,
+    /// Histogram of each trait in `TRAIT_RANGES` over the live agents
+    pub trait_histograms: [[u32; HISTOGRAM_BINS]; TRAIT_RANGES.len()],
+    /// Fraction of cells holding at least one agent
+    pub occupied_fraction: f64,
+}
+
+impl GpuStats {
+    pub fn buf_desc() -> wgpu::BufferDescriptor<'static> {
+        wgpu::BufferDescriptor {
+            label: Some("Stats Buffer"),
+            size: std::mem::size_of::<GpuStats>() as u64,
+            mapped_at_creation: false,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+        }
+    }
+
+    pub fn compute_shader_desc() -> wgpu::ShaderModuleDescriptor<'static> {
+        wgpu::ShaderModuleDescriptor {
+            label: Some("Stats Compute Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shader_compute_stats.wgsl").into()),
+        }
+    }
+
+    pub fn bind_layout_desc() -> wgpu::BindGroupLayoutDescriptor<'static> {
+        wgpu::BindGroupLayoutDescriptor {
+            label: Some("Stats Compute Bind Group Layout"),
+            entries: &[
+                // Env Buffer: the latest field
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Agent Buffer: the latest agent state
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Agent Texture: cells drawn to this step are occupied
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                // Stats Buffer: accumulated into, cleared every step
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        }
+    }
+}
+
+impl FrameStats {
+    pub fn from_gpu(frame: u64, raw: &GpuStats, cell_count: usize) -> Self {
+        let fixed = |lo: u32, hi: u32| ((hi as u64) << 32 | lo as u64) as f64 / FIXED_SCALE;
+        let pheromone_total = fixed(raw.pheromone_total_lo, raw.pheromone_total_hi);
+        Self {
+            frame,
+            pheromone_total,
+            pheromone_mean: pheromone_total / cell_count as f64,
+            pheromone_max: f32::from_bits(raw.pheromone_max),
+            level_histogram: raw.level_histogram,
+            mean_turn_speed: fixed(raw.turn_speed_total_lo, raw.turn_speed_total_hi)
+                / raw.population.max(1) as f64,
+            population: raw.population,
+            predators: raw.predators,
+            heading_histogram: raw.heading_histogram,
+            polarisation: polarisation(&raw.heading_histogram),
+            trait_histograms: std::array::from_fn(|t| {
+                std::array::from_fn(|i| raw.trait_histogram[t * HISTOGRAM_BINS + i])
+            }),
+            occupied_fraction: raw.occupied_cells as f64 / cell_count as f64,
+        }
+    }
+}
+
+/// Length of the mean heading of a heading histogram, taking each bin's agents to head at its
+/// centre
+fn polarisation(heading_histogram: &[u32; HISTOGRAM_BINS]) -> f64 {
+    let bin_width = std::f64::consts::TAU / HISTOGRAM_BINS as f64;
+    let (mut x, mut y, mut count) = (0.0, 0.0, 0.0);
+    for (i, &agents) in heading_histogram.iter().enumerate() {
+        let angle = (i as f64 + 0.5) * bin_width;
+        x += agents as f64 * angle.cos();
+        y += agents as f64 * angle.sin();
+        count += agents as f64;
+    }
+    if count == 0.0 {
+        return 0.0;
+    }
+    x.hypot(y) / count
+}
+
+/// Sends decoded statistics to a CSV file and/or the window title
+pub struct StatsRecorder {
+    csv: Option<BufWriter<File>>,
+    unflushed_rows: u32,
+    show_in_title: bool,
+    title: Option<String>,
+    /// Whether agents of species 1 are predators; particle life uses the species numbers for
+    /// its own species, so the predator count is left out otherwise
+    predators: bool,
+}
+
+impl StatsRecorder {
+    /// `None` if neither output is enabled, in which case the stats passes are skipped entirely
+    pub fn from_config(config: &Config) -> io::Result<Option<Self>> {
+        if config.stats_csv.is_none() && !config.stats_title {
+            return Ok(None);
+        }
+        let csv = match &config.stats_csv {
+            Some(path) => {
+                let mut csv = BufWriter::new(File::create(path)?);
+                write!(
+                    csv,
+                    "frame,pheromone_total,pheromone_mean,pheromone_max,occupied_fraction,mean_turn_speed,population,predators,polarisation"
+                )?;
+                for i in 0..HISTOGRAM_BINS {
+                    write!(csv, ",level_bin_{}", i)?;
+                }
+                for i in 0..HISTOGRAM_BINS {
+                    write!(csv, ",heading_bin_{}", i)?;
+                }
+                for (name, _) in TRAIT_RANGES {
+                    for i in 0..HISTOGRAM_BINS {
+                        write!(csv, ",{}_bin_{}", name, i)?;
+                    }
+                }
+                writeln!(csv)?;
+                Some(csv)
+            }
+            None => None,
+        };
+        Ok(Some(Self {
+            csv,
+            unflushed_rows: 0,
+            show_in_title: config.stats_title,
+            title: None,
+            predators: config.predators > 0,
+        }))
+    }
+
+    pub fn record(&mut self, stats: &FrameStats) -> io::Result<()> {
+        let predators = if self.predators {
+            stats.predators.to_string()
+        } else {
+            String::new()
+        };
+        if let Some(csv) = &mut self.csv {
+            write!(
+                csv,
+                "{},{},{},{},{},{},{},{},{}",
+                stats.frame,
+                stats.pheromone_total,
+                stats.pheromone_mean,
+                stats.pheromone_max,
+                stats.occupied_fraction,
+                stats.mean_turn_speed,
+                stats.population,
+                predators,
+                stats.polarisation
+            )?;
+            let traits = stats.trait_histograms.iter().flatten();
+            for bin in stats
+                .level_histogram
+                .iter()
+                .chain(&stats.heading_histogram)
+                .chain(traits)
+            {
+                write!(csv, ",{}", bin)?;
+            }
+            writeln!(csv)?;
+            self.unflushed_rows += 1;
+            if self.unflushed_rows >= CSV_FLUSH_ROWS {
+                self.unflushed_rows = 0;
+                csv.flush()?;
+            }
+        }
+        if self.show_in_title {
+            let mut title = format!(
+                "step {} | pheromone mean {:.4} max {:.3} | occupied {:.2}% | turn {:.4} | polarisation {:.2} | agents {}",
+                stats.frame,
+                stats.pheromone_mean,
+                stats.pheromone_max,
+                stats.occupied_fraction * 100.0,
+                stats.mean_turn_speed,
+                stats.polarisation,
+                stats.population
+            );
+            if self.predators {
+                title.push_str(&format!(" ({} predators)", predators));
+            }
+            self.title = Some(title);
+        }
+        Ok(())
+    }
+
+    /// Latest statistics for the window title, if enabled
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+}
+
+/// Writes out the rows since the last flush, reporting what `BufWriter` would drop silently
+impl Drop for StatsRecorder {
+    fn drop(&mut self) {
+        if let Some(csv) = &mut self.csv {
+            if let Err(e) = csv.flush() {
+                eprintln!("ERROR: Could not write stats: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn polarisation_is_the_length_of_the_mean_heading() {
+        let mut histogram = [0; HISTOGRAM_BINS];
+        assert_eq!(polarisation(&histogram), 0.0);
+
+        histogram[3] = 10;
+        assert!((polarisation(&histogram) - 1.0).abs() < 1e-9);
+
+        // Half heading the opposite way
+        histogram[3 + HISTOGRAM_BINS / 2] = 10;
+        assert!(polarisation(&histogram).abs() < 1e-9);
+
+        let uniform = [5; HISTOGRAM_BINS];
+        assert!(polarisation(&uniform).abs() < 1e-9);
+
+        // Three quarters one way, a quarter the other
+        let mut histogram = [0; HISTOGRAM_BINS];
+        histogram[0] = 30;
+        histogram[HISTOGRAM_BINS / 2] = 10;
+        assert!((polarisation(&histogram) - 0.5).abs() < 1e-9);
+    }
+}
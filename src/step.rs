@@ -0,0 +1,125 @@
+//! The order of work within one simulation step, and which half of each ping-pong buffer pair
+//! it reads and writes.
+//!
+//! A step runs these stages in order, all in a single command encoder:
+//!
+//! 1. **Spatial hash** (optional): buckets `agents[read]` by grid cell, for the behaviours that
+//!    look up neighbours.
+//! 2. **Sense, move, deposit** (agent pass): agents read `agents[read]` and sense `env[read]`,
+//!    write their new state to `agents[write]`, add their trail to the deposit buffer and draw
+//!    themselves to the (freshly cleared) agent texture. Organism cells only move here.
+//! 3. **Spawn** (optional, `--lifecycle`): places the siblings queued by the agent pass in free
+//!    slots of `agents[write]`.
+//! 4. **Bonds, settle** (optional, `--organisms`): the bond passes pull the organism cells in
+//!    `agents[write]` back into shape and draw the bonds, then the settle pass deposits and
+//!    draws the cells where they ended up.
+//! 5. **Hunt** (optional, `--predators`): predators eat the prey they reach in `agents[write]`.
+//! 6. **Diffuse, decay** (env pass): reads `env[read]` plus the deposits, writes the blurred and
+//!    decayed field to `env[write]` and the env texture. The deposit buffer is then cleared.
+//! 7. **Stats** (optional): summarises `env[write]` and `agents[write]`.
+//! 8. **Render**: draws the env and agent textures written by this step.
+//!
+//! After the step, `write` holds the latest state and becomes the next step's `read`.
+
+/// Indices into the `[forward, reverse]` agent and env buffer pairs for one step. Bind groups
+/// come in the same pairs, wired up as their pass's `PairBinding` describes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StepBuffers {
+    /// Holds the state at the start of the step
+    pub read: usize,
+    /// Receives the state at the end of the step
+    pub write: usize,
+}
+
+impl StepBuffers {
+    /// Buffers used by the step run after `steps_done` earlier steps
+    pub fn for_step(steps_done: u64) -> Self {
+        let read = Self::latest(steps_done);
+        Self {
+            read,
+            write: 1 - read,
+        }
+    }
+
+    /// The buffer holding the latest state once `steps_done` steps have run
+    pub fn latest(steps_done: u64) -> usize {
+        (steps_done % 2) as usize
+    }
+}
+
+/// How a pass uses the ping-pong pairs, which decides the buffers each bind group of its pair
+/// binds and which of the two a step uses
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PairBinding {
+    /// Reads one half and writes the other: the hash, agent and env passes
+    ReadToWrite,
+    /// Works in place on what the step wrote: the bond and stats passes
+    Written,
+}
+
+impl PairBinding {
+    /// Buffers that bind group `index` of the pair reads and writes
+    pub fn bound(self, index: usize) -> StepBuffers {
+        match self {
+            Self::ReadToWrite => StepBuffers {
+                read: index,
+                write: 1 - index,
+            },
+            Self::Written => StepBuffers {
+                read: index,
+                write: index,
+            },
+        }
+    }
+
+    /// The bind group of the pair used by a step working on `buffers`
+    pub fn index(self, buffers: StepBuffers) -> usize {
+        match self {
+            Self::ReadToWrite => buffers.read,
+            Self::Written => buffers.write,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_and_write_alternate_without_aliasing() {
+        for n in 0..4 {
+            let (buffers, next) = (StepBuffers::for_step(n), StepBuffers::for_step(n + 1));
+            assert_ne!(buffers.read, buffers.write, "step {}", n);
+            assert_eq!(next.read, buffers.write, "step {}", n);
+            assert_eq!(next.write, buffers.read, "step {}", n);
+        }
+    }
+
+    #[test]
+    fn latest_is_what_the_previous_step_wrote() {
+        assert_eq!(StepBuffers::latest(0), StepBuffers::for_step(0).read);
+        for n in 1..5 {
+            let previous = StepBuffers::for_step(n - 1);
+            assert_eq!(StepBuffers::latest(n), previous.write, "step {}", n);
+        }
+    }
+
+    #[test]
+    fn read_to_write_passes_get_the_step_buffers() {
+        let binding = PairBinding::ReadToWrite;
+        for n in 0..4 {
+            let buffers = StepBuffers::for_step(n);
+            assert_eq!(binding.bound(binding.index(buffers)), buffers, "step {}", n);
+        }
+    }
+
+    #[test]
+    fn written_passes_get_the_latest_state() {
+        let binding = PairBinding::Written;
+        for n in 0..4 {
+            let bound = binding.bound(binding.index(StepBuffers::for_step(n)));
+            assert_eq!(bound.read, StepBuffers::latest(n + 1), "step {}", n);
+            assert_eq!(bound.write, StepBuffers::latest(n + 1), "step {}", n);
+        }
+    }
+}